@@ -0,0 +1,224 @@
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::video_hub::{IdSelection, LockState};
+use crate::watch::parse_time;
+
+/// Whether a change was made by the client writing the audit log or reported
+/// by the router after another client, such as a panel, made it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOrigin {
+    Local,
+    External,
+}
+
+impl FromStr for AuditOrigin {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<AuditOrigin> {
+        match s {
+            "local" => Ok(AuditOrigin::Local),
+            "external" => Ok(AuditOrigin::External),
+            _ => Err(anyhow!("Unknown origin {}, expected local or external", s)),
+        }
+    }
+}
+
+/// A change with the value before and after it, ids are 0-based.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum AuditChange {
+    InputLabel { id: usize, from: String, to: String },
+    OutputLabel { id: usize, from: String, to: String },
+    Route { destination: usize, from: usize, to: usize },
+    Lock { destination: usize, from: LockState, to: LockState },
+}
+
+impl AuditChange {
+    /// The output a change applies to, `None` for input labels.
+    pub fn destination(&self) -> Option<usize> {
+        match self {
+            AuditChange::InputLabel { .. } => None,
+            AuditChange::OutputLabel { id, .. } => Some(*id),
+            AuditChange::Route { destination, .. } | AuditChange::Lock { destination, .. } => Some(*destination),
+        }
+    }
+}
+
+/// A line of the audit log.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// RFC 3339 UTC timestamp
+    pub time: String,
+    /// Unique id of the Videohub
+    pub hub: String,
+    pub origin: AuditOrigin,
+    #[serde(flatten)]
+    pub change: AuditChange,
+}
+
+impl AuditEntry {
+    /// A readable line with ids counted from `id_base`.
+    pub fn describe(&self, id_base: usize) -> String {
+        let origin = match self.origin {
+            AuditOrigin::Local => "local",
+            AuditOrigin::External => "external",
+        };
+        let change = match &self.change {
+            AuditChange::InputLabel { id, from, to } => format!("input label {}: {:?} -> {:?}", id + id_base, from, to),
+            AuditChange::OutputLabel { id, from, to } => format!("output label {}: {:?} -> {:?}", id + id_base, from, to),
+            AuditChange::Route { destination, from, to } => {
+                format!("route {}: {} -> {}", destination + id_base, from + id_base, to + id_base)
+            }
+            AuditChange::Lock { destination, from, to } => {
+                format!("lock {}: {} -> {}", destination + id_base, from.name(), to.name())
+            }
+        };
+        format!("{} {} {}", self.time, origin, change)
+    }
+}
+
+/// An append-only audit log with one JSON object per line (JSONL).
+///
+/// Entries are queued while a block from the router is applied and written
+/// together by [`AuditLog::flush`], so a line is never written in parts.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    file: File,
+    pending: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> anyhow::Result<AuditLog> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open audit log {}", path.display()))?;
+        Ok(AuditLog {
+            path: path.to_path_buf(),
+            file,
+            pending: Vec::new(),
+        })
+    }
+    pub fn push(&mut self, entry: AuditEntry) {
+        self.pending.push(entry);
+    }
+    /// Writes the queued entries to the file.
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let mut lines = String::new();
+        for entry in self.pending.drain(..) {
+            lines += &serde_json::to_string(&entry)?;
+            lines.push('\n');
+        }
+        self.file
+            .write_all(lines.as_bytes())
+            .and_then(|_| self.file.flush())
+            .with_context(|| format!("Failed to write audit log {}", self.path.display()))
+    }
+}
+
+/// Reads all entries of an audit log.
+pub fn read_audit_log(path: &Path) -> anyhow::Result<Vec<AuditEntry>> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            serde_json::from_str(line).with_context(|| format!("Invalid audit log entry on line {}", n + 1))
+        })
+        .collect()
+}
+
+/// Selects audit log entries by time, destination and origin.
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    /// Entries at or after this time since the Unix epoch
+    pub since: Option<Duration>,
+    /// Entries before this time since the Unix epoch
+    pub until: Option<Duration>,
+    /// Routes, locks and output labels of these 0-based outputs, `None` for all entries
+    pub destinations: Option<IdSelection>,
+    pub origin: Option<AuditOrigin>,
+}
+
+impl AuditQuery {
+    pub fn matches(&self, entry: &AuditEntry) -> anyhow::Result<bool> {
+        if self.since.is_some() || self.until.is_some() {
+            let time = parse_time(&entry.time)?;
+            if self.since.is_some_and(|since| time < since) || self.until.is_some_and(|until| time >= until) {
+                return Ok(false);
+            }
+        }
+        if let Some(destinations) = &self.destinations {
+            if !entry.change.destination().is_some_and(|destination| destinations.contains(destination)) {
+                return Ok(false);
+            }
+        }
+        Ok(self.origin.is_none_or(|origin| origin == entry.origin))
+    }
+    pub fn filter(&self, entries: Vec<AuditEntry>) -> anyhow::Result<Vec<AuditEntry>> {
+        let mut matching = Vec::new();
+        for entry in entries {
+            if self.matches(&entry)? {
+                matching.push(entry);
+            }
+        }
+        Ok(matching)
+    }
+}
+
+#[test]
+fn test_audit_query() {
+    let entry = |time: &str, change: AuditChange| AuditEntry {
+        time: time.to_string(),
+        hub: "7C2E0D03192A".to_string(),
+        origin: AuditOrigin::External,
+        change,
+    };
+    let entries = vec![
+        entry("2026-10-18T09:00:00.000Z", AuditChange::Route { destination: 2, from: 2, to: 5 }),
+        entry(
+            "2026-10-18T10:00:00.000Z",
+            AuditChange::InputLabel { id: 2, from: "Input 3".to_string(), to: "Cam 3".to_string() },
+        ),
+        entry(
+            "2026-10-18T11:00:00.000Z",
+            AuditChange::Lock { destination: 4, from: LockState::Unlocked, to: LockState::Locked },
+        ),
+    ];
+    let line = serde_json::to_string(&entries[0]).expect("Failed to serialize");
+    assert_eq!(
+        line,
+        r#"{"time":"2026-10-18T09:00:00.000Z","hub":"7C2E0D03192A","origin":"external","type":"route","destination":2,"from":2,"to":5}"#
+    );
+    assert_eq!(serde_json::from_str::<AuditEntry>(&line).expect("Failed to parse"), entries[0]);
+    assert_eq!(entries[2].describe(1), "2026-10-18T11:00:00.000Z external lock 5: unlocked -> locked");
+
+    let query = AuditQuery {
+        since: Some(parse_time("2026-10-18T10:00Z").expect("Failed to parse")),
+        ..Default::default()
+    };
+    assert_eq!(query.filter(entries.clone()).expect("Failed to filter").len(), 2);
+    let query = AuditQuery {
+        destinations: Some(IdSelection::Only(vec![0..=2])),
+        ..Default::default()
+    };
+    assert_eq!(query.filter(entries.clone()).expect("Failed to filter"), entries[0..1]);
+    let query = AuditQuery {
+        origin: Some(AuditOrigin::Local),
+        ..Default::default()
+    };
+    assert!(query.filter(entries).expect("Failed to filter").is_empty());
+}
@@ -3,7 +3,19 @@ use std::io::Read;
 use std::net::TcpStream;
 use std::time::{Duration, Instant};
 
+pub mod audit;
+pub mod metrics;
+pub mod mqtt;
+pub mod osc;
+pub mod salvo;
+pub mod schedule;
+pub mod shell;
+pub mod show;
+pub mod swp08;
+pub mod tsl;
+pub mod validate;
 pub mod video_hub;
+pub mod watch;
 
 #[macro_export]
 macro_rules! debug_println {
@@ -23,7 +35,7 @@ pub fn read_to_newline(s: &mut TcpStream, timeout: Option<Duration>) -> anyhow::
         if size > 0 {
             let str = core::str::from_utf8(&buf[..size])?;
             debug_println!("read data: len={}, {:?}", size, &buf[..size]);
-            result += &str;
+            result += str;
         }
 
         if result.ends_with("\n\n") && size != BUF_SIZE {
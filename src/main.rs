@@ -1,14 +1,36 @@
-use hub_util::video_hub::VideoHub;
+use hub_util::audit::{read_audit_log, AuditLog, AuditOrigin, AuditQuery};
+use hub_util::metrics::MetricsExporter;
+use hub_util::mqtt::{MqttBridge, MqttClient};
+use hub_util::osc::OscBridge;
+use hub_util::salvo::{apply_salvo, capture_salvo, salvo_diff, SalvoFile};
+use hub_util::schedule::{MissedPolicy, Schedule, Scheduler};
+use hub_util::shell::{self, Shell};
+use hub_util::show::{render_csv, render_grid, render_table, routing_entries};
+use hub_util::swp08::Swp08Gateway;
+use hub_util::tsl::{TslDisplay, TslSender, TslTransport, TslVersion};
+use hub_util::validate::validate_dump;
+use hub_util::watch::{parse_time, Watcher};
+use hub_util::video_hub::{DumpFormat, IdSelection, ImportFilter, VideoHub, VideoHubDump, VideoHubLabelType};
+use regex::Regex;
 use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
 
-use clap::{arg, command, Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Debug, Parser)] // requires `derive` feature
 #[command(about = "A CLI tool written in Rust for interacting with Blackmagic Videohub devices", long_about = None, version)]
-
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Number inputs and outputs from 1 like control panels instead of 0 like the Videohub protocol
+    #[arg(long, global = true)]
+    one_based: bool,
+    /// Appends every route, label and lock change seen while connected to this JSONL file
+    #[arg(long, global = true)]
+    audit_log: Option<String>,
 }
 #[derive(Debug, Subcommand)]
 enum Commands {
@@ -17,6 +39,12 @@ enum Commands {
     Dump {
         #[arg(short, long)]
         ip: String,
+        /// Writes the dump to a file instead of stdout, the format is picked from its extension
+        #[arg(short, long)]
+        file: Option<String>,
+        /// Dump format: json, yaml, toml or csv
+        #[arg(long)]
+        format: Option<DumpFormat>,
     },
     /// Loads all parameters from a Videohub dump file to a Videohub device
     Import {
@@ -24,37 +52,642 @@ enum Commands {
         ip: String,
         #[arg(short, long)]
         file: String,
+        /// Dump format: json, yaml, toml or csv, picked from the file extension by default
+        #[arg(long)]
+        format: Option<DumpFormat>,
+        /// Input labels to import: all, none or a list such as 0-7,10
+        #[arg(long, default_value = "all")]
+        sources: IdSelection,
+        /// Output labels to import: all, none or a list such as 0-7,10
+        #[arg(long, default_value = "all")]
+        destinations: IdSelection,
+        /// Routes to import by destination: all, none or a list such as 0-7,10
+        #[arg(long, default_value = "all")]
+        routes: IdSelection,
+        /// Prints the changes the import would make without sending anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Prints the dry run as JSON
+        #[arg(long, requires = "dry_run")]
+        json: bool,
+    },
+    /// Changes routes, e.g. `route 3=5 1-8=1-8 Program=Camera 2`
+    Route {
+        #[arg(short, long)]
+        ip: String,
+        /// Routes as destination=source, each side a number, a range or a label
+        #[arg(required = true)]
+        routes: Vec<String>,
+    },
+    /// Reads and changes input and output labels
+    Label {
+        #[arg(short, long)]
+        ip: String,
+        #[command(subcommand)]
+        action: LabelAction,
+    },
+    /// Shows the current routing as a table, or a crosspoint grid with --grid
+    Show {
+        #[arg(short, long)]
+        ip: String,
+        /// Draws a grid with a row per output and a column per input
+        #[arg(long, conflicts_with_all = ["json", "csv"])]
+        grid: bool,
+        #[arg(long, conflicts_with = "csv")]
+        json: bool,
+        #[arg(long)]
+        csv: bool,
+    },
+    /// Prints route, label, lock and input status changes as they happen
+    Watch {
+        #[arg(short, long)]
+        ip: String,
+        /// Prints one JSON object per line
+        #[arg(long)]
+        ndjson: bool,
+    },
+    /// Opens an interactive shell on a single connection, with history in ~/.hub_util_history
+    Shell {
+        #[arg(short, long)]
+        ip: String,
+        /// Lets undo also revert route and label changes made by other clients
+        #[arg(long)]
+        undo_external: bool,
+    },
+    /// Lists, previews, applies and captures salvos, named sets of routes
+    Salvo {
+        /// Salvo file, JSON, YAML or TOML depending on the extension
+        #[arg(short, long)]
+        file: String,
+        #[command(subcommand)]
+        action: SalvoAction,
+    },
+    /// Applies timed route, salvo and label changes from a schedule file
+    Schedule {
+        #[arg(short, long)]
+        ip: String,
+        /// Schedule file, JSON, YAML or TOML depending on the extension
+        #[arg(short, long)]
+        file: String,
+        /// File recording the last run, so events missed while hub_util was down are found
+        #[arg(long)]
+        state: Option<String>,
+        /// What to do with missed events: skip, run-all or run-latest, overrides the schedule file
+        #[arg(long)]
+        missed: Option<MissedPolicy>,
+    },
+    /// Lists changes from an audit log written with --audit-log
+    Audit {
+        #[arg(short, long)]
+        file: String,
+        /// Changes at or after this UTC time, e.g. 2026-10-18 or 2026-10-18T09:00Z
+        #[arg(long)]
+        since: Option<String>,
+        /// Changes before this UTC time
+        #[arg(long)]
+        until: Option<String>,
+        /// Routes, locks and labels of these outputs only, e.g. 0-7,10
+        #[arg(long)]
+        destinations: Option<IdSelection>,
+        /// Changes made by hub_util (local) or by other clients (external)
+        #[arg(long)]
+        origin: Option<AuditOrigin>,
+        /// Prints the matching entries as JSON lines
+        #[arg(long)]
+        json: bool,
+    },
+    /// Converts a dump between formats, e.g. a Videohub label file to JSON
+    Convert {
+        /// File to read, the format is picked from its extension unless --from is given
+        #[arg(short, long)]
+        input: String,
+        /// File to write, the format is picked from its extension unless --to is given
+        #[arg(short, long)]
+        output: String,
+        /// Format of the input: json, yaml, toml, csv or videohub
+        #[arg(long)]
+        from: Option<DumpFormat>,
+        /// Format of the output: json, yaml, toml, csv or videohub
+        #[arg(long)]
+        to: Option<DumpFormat>,
+    },
+    /// Prints the JSON Schema of the dump file format
+    Schema {},
+    /// Checks a dump file against the schema and for semantic errors such as duplicate ids
+    Validate {
+        #[arg(short, long)]
+        file: String,
+        /// Number of inputs of the target router, enables range checks of source ids
+        #[arg(long)]
+        inputs: Option<usize>,
+        /// Number of outputs of the target router, enables range checks of destination ids
+        #[arg(long)]
+        outputs: Option<usize>,
+    },
+    /// Bridges the Videohub state and commands to an MQTT broker
+    Mqtt {
+        #[arg(short, long)]
+        ip: String,
+        /// Address of the MQTT broker, port 1883 is used if none is given
+        #[arg(short, long)]
+        broker: String,
+        /// Topic prefix, the unique id of the Videohub is appended to it
+        #[arg(long, default_value = "videohub")]
+        prefix: String,
+        #[arg(long, default_value = "hub_util")]
+        client_id: String,
+    },
+    /// Listens for OSC messages over UDP and maps them onto the Videohub
+    Osc {
+        #[arg(short, long)]
+        ip: String,
+        /// Address to listen for OSC messages on
+        #[arg(short, long, default_value = "0.0.0.0:9000")]
+        listen: String,
+        /// Address to send feedback to when the router state changes, can be repeated
+        #[arg(short, long)]
+        feedback: Vec<String>,
+    },
+    /// Serves health and routing metrics of the Videohub for Prometheus
+    Metrics {
+        #[arg(short, long)]
+        ip: String,
+        /// Address to serve the `/metrics` endpoint on
+        #[arg(short, long, default_value = "0.0.0.0:9100")]
+        listen: String,
+    },
+    /// Sends the label of the source routed to each destination to TSL UMD displays
+    Tsl {
+        #[arg(short, long)]
+        ip: String,
+        /// Address of the UMD receiver
+        #[arg(short, long)]
+        target: String,
+        /// Protocol version, `3.1` or `5.0`
+        #[arg(long, default_value = "5.0")]
+        version: TslVersion,
+        /// Send over TCP instead of UDP
+        #[arg(long)]
+        tcp: bool,
+        /// Display mapping as `<destination>=<address>`, can be repeated.
+        /// Every destination is sent to the display with the same address if none are given
+        #[arg(short, long)]
+        map: Vec<TslDisplay>,
+    },
+    /// Accepts SW-P-08 (Probel) controllers and translates their requests to the Videohub
+    Swp08 {
+        #[arg(short, long)]
+        ip: String,
+        /// Address to accept SW-P-08 controllers on
+        #[arg(short, long, default_value = "0.0.0.0:8910")]
+        listen: String,
+    },
+}
+
+// An explicit format wins over the file extension, JSON is the default
+fn dump_format(format: Option<DumpFormat>, file: Option<&str>) -> DumpFormat {
+    format
+        .or_else(|| file.and_then(|file| DumpFormat::from_path(Path::new(file))))
+        .unwrap_or_default()
+}
+
+// Appends the default port to an address if it does not contain one
+fn with_default_port(addr: &str, port: u16) -> String {
+    if addr.contains(":") {
+        addr.to_owned()
+    } else {
+        format!("{addr}:{port}")
+    }
+}
+
+// Connects to a Videohub, logging its changes to the audit log if one is given
+fn connect_router(ip: &str, audit_log: Option<&String>) -> VideoHub {
+    let mut router = VideoHub::new(ip.parse().expect("Invalid IP address")).expect("Failed to connect to router");
+    if let Some(path) = audit_log {
+        router.set_audit_log(Some(AuditLog::open(Path::new(path)).expect("Failed to open audit log")));
+    }
+    router
+}
+
+fn resolve(addr: &str, port: u16) -> SocketAddr {
+    with_default_port(addr, port)
+        .to_socket_addrs()
+        .expect("Invalid address")
+        .next()
+        .expect("Address did not resolve")
+}
+
+#[derive(Debug, Subcommand)]
+enum LabelAction {
+    /// Prints labels as `id name` lines
+    Get {
+        kind: LabelKind,
+        /// Labels to print: all or a list such as 0-7,10
+        #[arg(default_value = "all")]
+        ids: IdSelection,
+    },
+    /// Sets a single label, or reads `id name` lines from a file or stdin if no id is given
+    Set {
+        kind: LabelKind,
+        #[arg(requires = "name")]
+        id: Option<usize>,
+        name: Option<String>,
+        /// File with `id name` lines, as printed by `label get`
+        #[arg(short, long, conflicts_with = "id")]
+        file: Option<String>,
+    },
+    /// Renames labels matching a regular expression, the replacement can use `$1` for groups
+    Replace {
+        kind: LabelKind,
+        pattern: String,
+        replacement: String,
+        /// Prints the new labels without sending them
+        #[arg(long)]
+        dry_run: bool,
     },
 }
 
-#[crate_type = "bin"]
+#[derive(Debug, Subcommand)]
+enum SalvoAction {
+    /// Lists the salvos in the file
+    List {},
+    /// Shows the routes a salvo would change
+    Preview {
+        #[arg(short, long)]
+        ip: String,
+        name: String,
+    },
+    /// Shows the routes a salvo changes and applies it as a single routing block
+    Apply {
+        #[arg(short, long)]
+        ip: String,
+        name: String,
+    },
+    /// Saves the current routing as a salvo, replacing one with the same name
+    Capture {
+        #[arg(short, long)]
+        ip: String,
+        name: String,
+        /// Outputs to capture: all or a list such as 0-7,10
+        #[arg(long, default_value = "all")]
+        destinations: IdSelection,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum LabelKind {
+    Input,
+    Output,
+}
+
+impl From<LabelKind> for VideoHubLabelType {
+    fn from(kind: LabelKind) -> Self {
+        match kind {
+            LabelKind::Input => VideoHubLabelType::Input,
+            LabelKind::Output => VideoHubLabelType::Output,
+        }
+    }
+}
+
 fn main() {
     let cli = Cli::parse();
+    let id_base = cli.one_based as usize;
+    let audit_log = cli.audit_log.as_ref();
 
     match &cli.command {
         Commands::Test {} => {}
-        Commands::Dump { ip } => {
-            let mut ip = ip.to_owned();
-            if !ip.contains(":") {
-                ip = format!("{ip}:9990");
-            }
+        Commands::Dump { ip, file, format } => {
+            let ip = with_default_port(ip, 9990);
+            let format = dump_format(*format, file.as_deref());
             let router = VideoHub::new(ip.parse().expect("Invalid IP address"));
-            let json = match router {
+            let dump = match router {
                 Err(e) => panic!("{e}"),
-                Ok(router) => router.dump_json().unwrap_or("".to_string()),
+                Ok(router) => router
+                    .dump()
+                    .with_id_base(id_base)
+                    .and_then(|dump| dump.to_format(format))
+                    .expect("Failed to serialize dump"),
+            };
+            match file {
+                Some(file) => fs::write(file, dump).expect("Failed to write file"),
+                None => println!("{}", dump),
+            }
+        }
+        Commands::Import {
+            ip,
+            file,
+            format,
+            sources,
+            destinations,
+            routes,
+            dry_run,
+            json,
+        } => {
+            let ip = with_default_port(ip, 9990);
+            let dump = fs::read_to_string(file).expect("Failed to read file");
+            let format = dump_format(*format, Some(file));
+            let dump = VideoHubDump::parse(&dump, format, id_base).expect("Failed to parse dump");
+            let filter = ImportFilter {
+                sources: sources.to_zero_based(id_base).expect("Invalid --sources"),
+                destinations: destinations.to_zero_based(id_base).expect("Invalid --destinations"),
+                routes: routes.to_zero_based(id_base).expect("Invalid --routes"),
+            };
+
+            let mut router = connect_router(&ip, audit_log);
+
+            if *dry_run {
+                let diff = router.diff(dump, &filter).expect("Failed to compare dump").with_id_base(id_base);
+                if *json {
+                    println!("{}", serde_json::to_string_pretty(&diff).expect("Failed to serialize diff"));
+                } else {
+                    print!("{}", diff);
+                }
+                return;
+            }
+
+            router.import_filtered(dump, &filter).expect("Failed to import dump");
+        }
+        Commands::Route { ip, routes } => {
+            let ip = with_default_port(ip, 9990);
+            let mut router = connect_router(&ip, audit_log);
+
+            let routes = router.parse_routes(routes, id_base).expect("Invalid route");
+            let mut destinations: Vec<usize> = Vec::new();
+            for route in &routes {
+                if !destinations.contains(&route.destination_id) {
+                    destinations.push(route.destination_id);
+                }
+            }
+            router.set_routes(routes).expect("Failed to set routes");
+
+            for destination in destinations {
+                let source = router.video_routes()[destination];
+                println!(
+                    "{} {} <- {} {}",
+                    destination + id_base,
+                    router.output_labels()[destination],
+                    source + id_base,
+                    router.input_labels()[source]
+                );
+            }
+        }
+        Commands::Label { ip, action } => {
+            let ip = with_default_port(ip, 9990);
+            let mut router = connect_router(&ip, audit_log);
+
+            match action {
+                LabelAction::Get { kind, ids } => {
+                    let ids = ids.to_zero_based(id_base).expect("Invalid ids");
+                    for (id, label) in router.labels((*kind).into()).iter().enumerate() {
+                        if ids.contains(id) {
+                            println!("{} {}", id + id_base, label);
+                        }
+                    }
+                }
+                LabelAction::Set { kind, id, name, file } => {
+                    let kind = (*kind).into();
+                    if let (Some(id), Some(name)) = (id, name) {
+                        let id = id.checked_sub(id_base).expect("Invalid id");
+                        router.set_label(kind, id, name).expect("Failed to set label");
+                        return;
+                    }
+
+                    let text = match file {
+                        Some(file) => fs::read_to_string(file).expect("Failed to read file"),
+                        None => {
+                            let mut text = String::new();
+                            std::io::stdin().read_to_string(&mut text).expect("Failed to read stdin");
+                            text
+                        }
+                    };
+                    let labels = router.parse_label_lines(kind, &text, id_base).expect("Invalid labels");
+                    if !labels.is_empty() {
+                        router.set_labels(kind, labels).expect("Failed to set labels");
+                    }
+                }
+                LabelAction::Replace {
+                    kind,
+                    pattern,
+                    replacement,
+                    dry_run,
+                } => {
+                    let kind = (*kind).into();
+                    let pattern = Regex::new(pattern).expect("Invalid regular expression");
+                    let labels = router.replace_labels(kind, &pattern, replacement);
+                    for label in &labels {
+                        println!("{} {} -> {}", label.id + id_base, router.labels(kind)[label.id], label.name);
+                    }
+                    if !*dry_run && !labels.is_empty() {
+                        router.set_labels(kind, labels).expect("Failed to set labels");
+                    }
+                }
+            }
+        }
+        Commands::Show { ip, grid, json, csv } => {
+            let ip = with_default_port(ip, 9990);
+            let router = connect_router(&ip, audit_log);
+
+            let entries = routing_entries(&router, id_base);
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&entries).expect("Failed to serialize routing"));
+            } else if *csv {
+                print!("{}", render_csv(&entries).expect("Failed to create CSV"));
+            } else if *grid {
+                print!("{}", render_grid(&entries, router.input_count(), id_base));
+            } else {
+                print!("{}", render_table(&entries));
+            }
+        }
+        Commands::Watch { ip, ndjson } => {
+            let ip = with_default_port(ip, 9990);
+            let router = connect_router(&ip, audit_log);
+
+            let mut watcher = Watcher::new(router, std::io::stdout(), *ndjson, id_base);
+            watcher.run().expect("Lost connection to router");
+        }
+        Commands::Shell { ip, undo_external } => {
+            let ip = with_default_port(ip, 9990);
+            let mut router = connect_router(&ip, audit_log);
+            router.record_external_changes(*undo_external);
+
+            let history = std::env::var_os("HOME").map(|home| Path::new(&home).join(".hub_util_history"));
+            shell::run(Shell::new(router, id_base), history).expect("Shell failed");
+        }
+        Commands::Salvo { file, action } => {
+            let path = Path::new(file);
+            let connect = |ip: &str| {
+                let ip = with_default_port(ip, 9990);
+                connect_router(&ip, audit_log)
             };
-            println!("{}", json);
+
+            match action {
+                SalvoAction::List {} => {
+                    let salvos = SalvoFile::load(path).expect("Failed to load salvos");
+                    for salvo in &salvos.salvos {
+                        println!("{} ({} routes)", salvo.name, salvo.routes.len());
+                    }
+                }
+                SalvoAction::Preview { ip, name } => {
+                    let salvos = SalvoFile::load(path).expect("Failed to load salvos");
+                    let salvo = salvos.get(name).expect("Unknown salvo");
+                    let router = connect(ip);
+                    let diff = salvo_diff(&router, salvo, salvos.id_base).expect("Failed to compare salvo");
+                    print!("{}", diff.with_id_base(id_base));
+                }
+                SalvoAction::Apply { ip, name } => {
+                    let salvos = SalvoFile::load(path).expect("Failed to load salvos");
+                    let salvo = salvos.get(name).expect("Unknown salvo");
+                    let mut router = connect(ip);
+                    let diff = salvo_diff(&router, salvo, salvos.id_base).expect("Failed to compare salvo");
+                    print!("{}", diff.with_id_base(id_base));
+                    apply_salvo(&mut router, salvo, salvos.id_base).expect("Failed to apply salvo");
+                }
+                SalvoAction::Capture { ip, name, destinations } => {
+                    // a new file is numbered like the command line
+                    let mut salvos = match path.exists() {
+                        true => SalvoFile::load(path).expect("Failed to load salvos"),
+                        false => SalvoFile {
+                            id_base,
+                            salvos: Vec::new(),
+                        },
+                    };
+                    let router = connect(ip);
+                    let destinations = destinations.to_zero_based(id_base).expect("Invalid destinations");
+                    let salvo = capture_salvo(&router, name, &destinations, salvos.id_base);
+                    println!("Captured {} routes as {}", salvo.routes.len(), salvo.name);
+                    salvos.insert(salvo);
+                    salvos.save(path).expect("Failed to save salvos");
+                }
+            }
         }
-        Commands::Import { ip, file } => {
-            let mut ip = ip.to_owned();
-            if !ip.contains(":") {
-                ip = format!("{ip}:9990");
+        Commands::Schedule { ip, file, state, missed } => {
+            let ip = with_default_port(ip, 9990);
+            let mut schedule = Schedule::load(Path::new(file)).expect("Failed to load schedule");
+            if let Some(missed) = missed {
+                schedule.missed = *missed;
+            }
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("Invalid system time").as_secs();
+
+            let events = schedule.events.len();
+            let mut scheduler = Scheduler::new(ip.parse().expect("Invalid IP address"), schedule, state.as_ref().map(PathBuf::from), now)
+                .expect("Invalid schedule");
+            scheduler.audit_log = audit_log.map(PathBuf::from);
+            println!("Running {} scheduled events", events);
+            scheduler.run().expect("Scheduler stopped");
+        }
+        Commands::Audit {
+            file,
+            since,
+            until,
+            destinations,
+            origin,
+            json,
+        } => {
+            let query = AuditQuery {
+                since: since.as_ref().map(|time| parse_time(time).expect("Invalid --since time")),
+                until: until.as_ref().map(|time| parse_time(time).expect("Invalid --until time")),
+                destinations: destinations
+                    .as_ref()
+                    .map(|destinations| destinations.to_zero_based(id_base).expect("Invalid destinations")),
+                origin: *origin,
+            };
+            let entries = read_audit_log(Path::new(file)).expect("Failed to read audit log");
+            for entry in query.filter(entries).expect("Failed to query audit log") {
+                match json {
+                    true => println!("{}", serde_json::to_string(&entry).expect("Failed to serialize entry")),
+                    false => println!("{}", entry.describe(id_base)),
+                }
             }
+        }
+        Commands::Convert { input, output, from, to } => {
+            let text = fs::read_to_string(input).expect("Failed to read file");
+            let dump = VideoHubDump::parse(&text, dump_format(*from, Some(input)), id_base).expect("Failed to parse dump");
+            let converted = dump
+                .with_id_base(id_base)
+                .and_then(|dump| dump.to_format(dump_format(*to, Some(output))))
+                .expect("Failed to convert dump");
+            fs::write(output, converted).expect("Failed to write file");
+        }
+        Commands::Schema {} => {
+            let schema = VideoHubDump::json_schema();
+            println!("{}", serde_json::to_string_pretty(&schema).expect("Failed to serialize schema"));
+        }
+        Commands::Validate { file, inputs, outputs } => {
             let dump = fs::read_to_string(file).expect("Failed to read file");
 
-            let mut router = VideoHub::new(ip.parse().expect("Invalid IP address")).expect("Failed to connect to router");
+            let errors = validate_dump(&dump, *inputs, *outputs);
+            if errors.is_empty() {
+                println!("{} is valid", file);
+                return;
+            }
+            for error in &errors {
+                println!("{}", error);
+            }
+            eprintln!("{} contains {} error(s)", file, errors.len());
+            std::process::exit(1);
+        }
+        Commands::Mqtt { ip, broker, prefix, client_id } => {
+            let ip = with_default_port(ip, 9990);
+            let router = connect_router(&ip, audit_log);
+            let broker = resolve(broker, 1883);
+            let client = MqttClient::connect(broker, client_id).expect("Failed to connect to MQTT broker");
+
+            let mut bridge = MqttBridge::new(router, client, prefix);
+            println!("Bridging to MQTT under {}", bridge.base_topic());
+            bridge.run().expect("MQTT bridge stopped");
+        }
+        Commands::Osc { ip, listen, feedback } => {
+            let ip = with_default_port(ip, 9990);
+            let router = connect_router(&ip, audit_log);
+            let socket = UdpSocket::bind(listen).expect("Failed to bind OSC socket");
+            let feedback = feedback.iter().map(|addr| resolve(addr, 9001)).collect();
+
+            let mut bridge = OscBridge::new(router, socket, feedback);
+            println!("Listening for OSC messages on {}", listen);
+            bridge.run().expect("OSC bridge stopped");
+        }
+        Commands::Metrics { ip, listen } => {
+            let ip = with_default_port(ip, 9990);
+            let listener = TcpListener::bind(listen).expect("Failed to bind metrics listener");
+
+            let mut exporter =
+                MetricsExporter::new(ip.parse().expect("Invalid IP address"), listener).expect("Failed to start exporter");
+            exporter.audit_log = audit_log.map(PathBuf::from);
+            println!("Serving metrics on http://{}/metrics", listen);
+            exporter.run().expect("Metrics exporter stopped");
+        }
+        Commands::Tsl { ip, target, version, tcp, map } => {
+            let ip = with_default_port(ip, 9990);
+            let router = connect_router(&ip, audit_log);
+            let target = resolve(target, 8900);
+
+            let transport = if *tcp {
+                TslTransport::Tcp(TcpStream::connect(target).expect("Failed to connect to UMD receiver"))
+            } else {
+                let socket = UdpSocket::bind("0.0.0.0:0").expect("Failed to bind UDP socket");
+                TslTransport::Udp(socket, target)
+            };
+
+            let map = map
+                .iter()
+                .map(|display| TslDisplay {
+                    destination: display.destination.checked_sub(id_base).expect("Destination ids start at 1"),
+                    address: display.address,
+                })
+                .collect();
+
+            let mut sender = TslSender::new(router, transport, *version, map).expect("Invalid display mapping");
+            println!("Sending TSL UMD to {}", target);
+            sender.run().expect("TSL sender stopped");
+        }
+        Commands::Swp08 { ip, listen } => {
+            let ip = with_default_port(ip, 9990);
+            let router = connect_router(&ip, audit_log);
+            let listener = TcpListener::bind(listen).expect("Failed to bind SW-P-08 listener");
 
-            router.import_dump(&dump).expect("Failed to import dump");
+            let mut gateway = Swp08Gateway::new(router, listener).expect("Failed to start gateway");
+            println!("Accepting SW-P-08 controllers on {}", listen);
+            gateway.run().expect("SW-P-08 gateway stopped");
         }
     }
 }
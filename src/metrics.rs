@@ -0,0 +1,293 @@
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddrV4, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::audit::AuditLog;
+use crate::debug_println;
+use crate::video_hub::{CommandStats, HubChange, LockState, VideoHub};
+
+/// Time a scraper has to send its whole request before it is disconnected
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+// a scraper whose request has not been read completely yet
+struct MetricsClient {
+    stream: TcpStream,
+    request: Vec<u8>,
+    accepted: Instant,
+}
+
+/// Keeps a session with a Videohub open, reconnecting when it drops, and serves
+/// its health and state in the Prometheus text format on `/metrics`.
+pub struct MetricsExporter {
+    addr: SocketAddrV4,
+    listener: TcpListener,
+    clients: Vec<MetricsClient>,
+    hub: Option<VideoHub>,
+    connected_once: bool,
+    last_attempt: Option<Instant>,
+    last_ping: Instant,
+    reconnects: u64,
+    // stats of sessions that have been closed, so counters survive reconnects
+    previous_stats: CommandStats,
+    route_changes: Vec<u64>,
+    pub retry_interval: Duration,
+    pub ping_interval: Duration,
+    /// Audit log every session with the Videohub appends to
+    pub audit_log: Option<PathBuf>,
+}
+
+impl MetricsExporter {
+    pub fn new(addr: SocketAddrV4, listener: TcpListener) -> anyhow::Result<MetricsExporter> {
+        listener.set_nonblocking(true)?;
+        Ok(MetricsExporter {
+            addr,
+            listener,
+            clients: vec![],
+            hub: None,
+            connected_once: false,
+            last_attempt: None,
+            last_ping: Instant::now(),
+            reconnects: 0,
+            previous_stats: CommandStats::default(),
+            route_changes: vec![],
+            retry_interval: Duration::from_secs(5),
+            ping_interval: Duration::from_secs(10),
+            audit_log: None,
+        })
+    }
+    pub fn is_connected(&self) -> bool {
+        self.hub.is_some()
+    }
+    /// Runs one iteration: keeps the Videohub session alive and answers pending scrapes.
+    pub fn step(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        if self.hub.is_none() {
+            self.connect();
+        }
+
+        if let Some(hub) = &mut self.hub {
+            let result = hub.poll(timeout).and_then(|changes| {
+                if self.last_ping.elapsed() > self.ping_interval {
+                    self.last_ping = Instant::now();
+                    hub.ping()?;
+                }
+                Ok(changes)
+            });
+            match result {
+                Ok(changes) => {
+                    for change in changes {
+                        if let HubChange::Route { destination, .. } = change {
+                            if let Some(count) = self.route_changes.get_mut(destination) {
+                                *count += 1;
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Lost connection to VideoHub at {}: {}", self.addr, e);
+                    self.disconnect();
+                }
+            }
+        } else {
+            std::thread::sleep(timeout);
+        }
+
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = stream.set_nonblocking(true) {
+                        debug_println!("Failed to accept metrics request: {}", e);
+                        continue;
+                    }
+                    self.clients.push(MetricsClient {
+                        stream,
+                        request: vec![],
+                        accepted: Instant::now(),
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        // requests are read without blocking, so a slow scraper does not stall
+        // the Videohub session, and dropped when not complete in time
+        for mut client in std::mem::take(&mut self.clients) {
+            match self.handle_request(&mut client) {
+                Ok(true) => {}
+                Ok(false) if client.accepted.elapsed() < REQUEST_TIMEOUT => self.clients.push(client),
+                Ok(false) => debug_println!("Metrics request not received in time"),
+                Err(e) => debug_println!("Failed to answer metrics request: {}", e),
+            }
+        }
+        Ok(())
+    }
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        loop {
+            self.step(Duration::from_millis(100))?;
+        }
+    }
+    fn connect(&mut self) {
+        if self
+            .last_attempt
+            .is_some_and(|attempt| attempt.elapsed() < self.retry_interval)
+        {
+            return;
+        }
+        self.last_attempt = Some(Instant::now());
+
+        let hub = VideoHub::new(self.addr).and_then(|mut hub| {
+            if let Some(path) = &self.audit_log {
+                hub.set_audit_log(Some(AuditLog::open(path)?));
+            }
+            Ok(hub)
+        });
+        match hub {
+            Ok(hub) => {
+                if self.connected_once {
+                    self.reconnects += 1;
+                }
+                self.connected_once = true;
+                self.route_changes.resize(hub.output_count(), 0);
+                self.last_ping = Instant::now();
+                self.hub = Some(hub);
+            }
+            Err(e) => debug_println!("Failed to connect to VideoHub at {}: {}", self.addr, e),
+        }
+    }
+    fn disconnect(&mut self) {
+        if let Some(hub) = self.hub.take() {
+            let stats = hub.command_stats();
+            self.previous_stats.sent += stats.sent;
+            self.previous_stats.nacks += stats.nacks;
+            self.previous_stats.latency_total += stats.latency_total;
+        }
+    }
+    // reads what the client has sent so far and answers once the request is
+    // complete, returns whether it was answered
+    fn handle_request(&self, client: &mut MetricsClient) -> anyhow::Result<bool> {
+        let mut buf = [0u8; 512];
+        while !client.request.ends_with(b"\r\n\r\n") {
+            match client.stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(size) => client.request.extend_from_slice(&buf[..size]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let request = String::from_utf8_lossy(&client.request);
+        let response = match request.split(' ').nth(1) {
+            Some("/metrics") => {
+                let body = self.render();
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            }
+            _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+        };
+        // the response is small enough for the socket buffer
+        client.stream.set_nonblocking(false)?;
+        client.stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+        client.stream.write_all(response.as_bytes())?;
+        Ok(true)
+    }
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut stats = self.previous_stats.clone();
+        if let Some(hub) = &self.hub {
+            stats.sent += hub.command_stats().sent;
+            stats.nacks += hub.command_stats().nacks;
+            stats.latency_total += hub.command_stats().latency_total;
+        }
+
+        let mut out = String::new();
+        metric_header(&mut out, "videohub_up", "gauge", "Whether the Videohub is connected");
+        let _ = writeln!(out, "videohub_up {}", self.hub.is_some() as u8);
+        metric_header(&mut out, "videohub_reconnects_total", "counter", "Number of times the connection was re-established");
+        let _ = writeln!(out, "videohub_reconnects_total {}", self.reconnects);
+        metric_header(&mut out, "videohub_commands_total", "counter", "Requests sent to the Videohub");
+        let _ = writeln!(out, "videohub_commands_total {}", stats.sent);
+        metric_header(&mut out, "videohub_nacks_total", "counter", "Requests rejected by the Videohub");
+        let _ = writeln!(out, "videohub_nacks_total {}", stats.nacks);
+        metric_header(&mut out, "videohub_command_latency_seconds", "summary", "Time until a request was acknowledged");
+        let _ = writeln!(out, "videohub_command_latency_seconds_sum {}", stats.latency_total.as_secs_f64());
+        let _ = writeln!(out, "videohub_command_latency_seconds_count {}", stats.sent);
+
+        metric_header(&mut out, "videohub_route_changes_total", "counter", "Routing changes per destination");
+        for (destination, count) in self.route_changes.iter().enumerate() {
+            let _ = writeln!(out, "videohub_route_changes_total{{destination=\"{}\"}} {}", destination, count);
+        }
+
+        let hub = match &self.hub {
+            Some(hub) => hub,
+            None => return out,
+        };
+
+        metric_header(&mut out, "videohub_info", "gauge", "Model of the connected Videohub");
+        let _ = writeln!(
+            out,
+            "videohub_info{{model=\"{}\",unique_id=\"{}\"}} 1",
+            escape_label(hub.model()),
+            escape_label(hub.unique_id())
+        );
+
+        metric_header(&mut out, "videohub_route_info", "gauge", "Source currently routed to each destination");
+        for (destination, source) in hub.video_routes().iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "videohub_route_info{{destination=\"{}\",destination_label=\"{}\",source=\"{}\",source_label=\"{}\"}} 1",
+                destination,
+                escape_label(hub.output_labels().get(destination).map_or("", |s| s)),
+                source,
+                escape_label(hub.input_labels().get(*source).map_or("", |s| s)),
+            );
+        }
+
+        metric_header(&mut out, "videohub_output_locked", "gauge", "Lock state per destination, 0 unlocked, 1 locked by this client, 2 locked by another client");
+        for (destination, state) in hub.output_locks().iter().enumerate() {
+            let value = match state {
+                LockState::Unlocked => 0,
+                LockState::Owned => 1,
+                LockState::Locked => 2,
+            };
+            let _ = writeln!(out, "videohub_output_locked{{destination=\"{}\"}} {}", destination, value);
+        }
+
+        metric_header(&mut out, "videohub_input_status_info", "gauge", "Connector type reported for each input");
+        for (input, status) in hub.input_status().iter().enumerate() {
+            if status.is_empty() {
+                continue;
+            }
+            let _ = writeln!(
+                out,
+                "videohub_input_status_info{{input=\"{}\",status=\"{}\"}} 1",
+                input,
+                escape_label(status)
+            );
+        }
+
+        out
+    }
+}
+
+fn metric_header(out: &mut String, name: &str, metric_type: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, metric_type);
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[test]
+fn test_escape_label() {
+    assert_eq!(escape_label("Cam 1"), "Cam 1");
+    assert_eq!(escape_label("Say \"hi\"\\\n"), "Say \\\"hi\\\"\\\\\\n");
+}
@@ -0,0 +1,348 @@
+use anyhow::{anyhow, Context};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use crate::debug_println;
+use crate::video_hub::{HubChange, VideoHub, VideoHubLabelType, VideoHubRoute};
+
+/// Minimal MQTT 3.1.1 client, only supports QoS 0 which is all the bridge needs.
+#[derive(Debug)]
+pub struct MqttClient {
+    stream: TcpStream,
+    keep_alive: Duration,
+    last_sent: Instant,
+    next_packet_id: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttPublish {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub retain: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum MqttPacket {
+    ConnAck(u8),
+    Publish(MqttPublish),
+    SubAck,
+    PingResp,
+    Other(u8),
+}
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const SUBSCRIBE: u8 = 0x82;
+const SUBACK: u8 = 0x90;
+const PINGREQ: u8 = 0xC0;
+const PINGRESP: u8 = 0xD0;
+const DISCONNECT: u8 = 0xE0;
+
+fn encode_remaining_length(mut len: usize) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        encoded.push(byte);
+        if len == 0 {
+            return encoded;
+        }
+    }
+}
+
+#[test]
+fn test_encode_remaining_length() {
+    assert_eq!(encode_remaining_length(0), vec![0x00]);
+    assert_eq!(encode_remaining_length(127), vec![0x7F]);
+    assert_eq!(encode_remaining_length(128), vec![0x80, 0x01]);
+    assert_eq!(encode_remaining_length(16_383), vec![0xFF, 0x7F]);
+    assert_eq!(encode_remaining_length(2_097_152), vec![0x80, 0x80, 0x80, 0x01]);
+}
+
+fn encode_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn encode_packet(packet_type: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![packet_type];
+    packet.extend(encode_remaining_length(body.len()));
+    packet.extend_from_slice(body);
+    packet
+}
+
+impl MqttPublish {
+    fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        encode_string(&mut body, &self.topic);
+        body.extend_from_slice(&self.payload);
+        encode_packet(PUBLISH | self.retain as u8, &body)
+    }
+    fn decode(flags: u8, body: &[u8]) -> anyhow::Result<MqttPublish> {
+        let topic_len = u16::from_be_bytes(
+            body.get(..2)
+                .ok_or_else(|| anyhow!("PUBLISH packet too short"))?
+                .try_into()?,
+        ) as usize;
+        let topic = body
+            .get(2..2 + topic_len)
+            .ok_or_else(|| anyhow!("PUBLISH topic truncated"))?;
+        let mut payload_start = 2 + topic_len;
+        // QoS 1 and 2 messages carry a packet identifier before the payload
+        if flags & 0x06 != 0 {
+            payload_start += 2;
+        }
+        Ok(MqttPublish {
+            topic: String::from_utf8(topic.to_vec())?,
+            payload: body.get(payload_start..).unwrap_or_default().to_vec(),
+            retain: flags & 0x01 != 0,
+        })
+    }
+}
+
+#[test]
+fn test_publish_round_trip() {
+    let publish = MqttPublish {
+        topic: "videohub/test/route/0".to_string(),
+        payload: b"5".to_vec(),
+        retain: true,
+    };
+    let encoded = publish.encode();
+    assert_eq!(encoded[0], 0x31);
+    assert_eq!(encoded[1] as usize, encoded.len() - 2);
+
+    let decoded = MqttPublish::decode(encoded[0] & 0x0F, &encoded[2..]).expect("Failed to decode publish");
+    assert_eq!(decoded, publish);
+}
+
+impl MqttClient {
+    pub fn connect(addr: SocketAddr, client_id: &str) -> anyhow::Result<MqttClient> {
+        let stream = TcpStream::connect_timeout(&addr, Duration::from_secs(5))?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+        let mut client = MqttClient {
+            stream,
+            keep_alive: Duration::from_secs(60),
+            last_sent: Instant::now(),
+            next_packet_id: 1,
+        };
+
+        let mut body = Vec::new();
+        encode_string(&mut body, "MQTT");
+        // protocol level 4 (3.1.1), clean session
+        body.extend_from_slice(&[0x04, 0x02]);
+        body.extend_from_slice(&(client.keep_alive.as_secs() as u16).to_be_bytes());
+        encode_string(&mut body, client_id);
+        client.send(&encode_packet(CONNECT, &body))?;
+
+        match client.read_packet()? {
+            MqttPacket::ConnAck(0) => Ok(client),
+            MqttPacket::ConnAck(code) => Err(anyhow!("Broker refused connection, return code {}", code)),
+            packet => Err(anyhow!("Expected CONNACK from broker, got {:?}", packet)),
+        }
+    }
+    fn send(&mut self, packet: &[u8]) -> anyhow::Result<()> {
+        self.last_sent = Instant::now();
+        self.stream
+            .write_all(packet)
+            .with_context(|| "Failed to write packet to broker")
+    }
+    fn read_packet(&mut self) -> anyhow::Result<MqttPacket> {
+        let mut header = [0u8; 1];
+        self.stream.read_exact(&mut header)?;
+
+        let mut len = 0usize;
+        let mut multiplier = 1usize;
+        loop {
+            let mut byte = [0u8; 1];
+            self.stream.read_exact(&mut byte)?;
+            len += (byte[0] & 0x7F) as usize * multiplier;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+            multiplier *= 128;
+            if multiplier > 128 * 128 * 128 {
+                return Err(anyhow!("Malformed remaining length from broker"));
+            }
+        }
+
+        let mut body = vec![0u8; len];
+        self.stream.read_exact(&mut body)?;
+
+        Ok(match header[0] & 0xF0 {
+            CONNACK => MqttPacket::ConnAck(*body.get(1).unwrap_or(&0xFF)),
+            PUBLISH => MqttPacket::Publish(MqttPublish::decode(header[0] & 0x0F, &body)?),
+            SUBACK => MqttPacket::SubAck,
+            PINGRESP => MqttPacket::PingResp,
+            other => MqttPacket::Other(other),
+        })
+    }
+    pub fn publish(&mut self, topic: &str, payload: &[u8], retain: bool) -> anyhow::Result<()> {
+        let publish = MqttPublish {
+            topic: topic.to_string(),
+            payload: payload.to_vec(),
+            retain,
+        };
+        self.send(&publish.encode())
+    }
+    pub fn subscribe(&mut self, topics: &[String]) -> anyhow::Result<()> {
+        let packet_id = self.next_packet_id;
+        self.next_packet_id = self.next_packet_id.wrapping_add(1).max(1);
+
+        let mut body = packet_id.to_be_bytes().to_vec();
+        for topic in topics {
+            encode_string(&mut body, topic);
+            body.push(0x00);
+        }
+        self.send(&encode_packet(SUBSCRIBE, &body))
+    }
+    /// Waits up to `timeout` for a message on one of the subscribed topics.
+    pub fn poll(&mut self, timeout: Duration) -> anyhow::Result<Option<MqttPublish>> {
+        if self.last_sent.elapsed() > self.keep_alive / 2 {
+            self.send(&[PINGREQ, 0x00])?;
+        }
+
+        self.stream.set_read_timeout(Some(timeout))?;
+        let mut buf = [0u8; 1];
+        let available = match self.stream.peek(&mut buf) {
+            Ok(0) => Err(anyhow!("Connection closed by broker")),
+            Ok(_) => Ok(true),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(false),
+            Err(e) => Err(e.into()),
+        };
+        self.stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+        if !available? {
+            return Ok(None);
+        }
+        match self.read_packet()? {
+            MqttPacket::Publish(publish) => Ok(Some(publish)),
+            packet => {
+                debug_println!("Ignoring MQTT packet: {:?}", packet);
+                Ok(None)
+            }
+        }
+    }
+    pub fn disconnect(mut self) -> anyhow::Result<()> {
+        self.send(&[DISCONNECT, 0x00])
+    }
+}
+
+/// Publishes the state of a Videohub as retained topics and applies commands
+/// received on the matching `/set` topics.
+///
+/// Topics, all ids are 0-based:
+/// - `<base>/route/<dest>` routed source of a destination
+/// - `<base>/label/input/<n>` and `<base>/label/output/<n>` labels
+/// - the same topics suffixed with `/set` change the router
+pub struct MqttBridge {
+    hub: VideoHub,
+    client: MqttClient,
+    base_topic: String,
+}
+
+impl MqttBridge {
+    /// `prefix` is followed by the unique id of the router, e.g. `videohub/7C2E0D03192A`
+    pub fn new(hub: VideoHub, client: MqttClient, prefix: &str) -> MqttBridge {
+        let base_topic = format!("{}/{}", prefix, hub.unique_id());
+        MqttBridge {
+            hub,
+            client,
+            base_topic,
+        }
+    }
+    pub fn hub(&self) -> &VideoHub {
+        &self.hub
+    }
+    pub fn base_topic(&self) -> &str {
+        &self.base_topic
+    }
+    /// Publishes the full state and subscribes to the command topics.
+    pub fn start(&mut self) -> anyhow::Result<()> {
+        let base = &self.base_topic;
+        self.client.publish(&format!("{base}/model"), self.hub.model().as_bytes(), true)?;
+        for (index, name) in self.hub.input_labels().iter().enumerate() {
+            self.client.publish(&format!("{base}/label/input/{index}"), name.as_bytes(), true)?;
+        }
+        for (index, name) in self.hub.output_labels().iter().enumerate() {
+            self.client.publish(&format!("{base}/label/output/{index}"), name.as_bytes(), true)?;
+        }
+        for (destination, source) in self.hub.video_routes().iter().enumerate() {
+            self.client
+                .publish(&format!("{base}/route/{destination}"), source.to_string().as_bytes(), true)?;
+        }
+
+        self.client.subscribe(&[
+            format!("{base}/route/+/set"),
+            format!("{base}/label/+/+/set"),
+        ])
+    }
+    /// Runs one iteration of the bridge, waiting up to `timeout` for each side.
+    pub fn step(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        if let Some(publish) = self.client.poll(timeout)? {
+            if let Err(e) = self.handle_command(&publish) {
+                eprintln!("Failed to apply MQTT command on {}: {}", publish.topic, e);
+            }
+        }
+
+        for change in self.hub.poll(timeout)? {
+            self.publish_change(&change)?;
+        }
+
+        Ok(())
+    }
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        self.start()?;
+        loop {
+            self.step(Duration::from_millis(50))?;
+        }
+    }
+    fn publish_change(&mut self, change: &HubChange) -> anyhow::Result<()> {
+        let base = &self.base_topic;
+        let (topic, payload) = match change {
+            HubChange::InputLabel { index, name } => (format!("{base}/label/input/{index}"), name.clone()),
+            HubChange::OutputLabel { index, name } => (format!("{base}/label/output/{index}"), name.clone()),
+            HubChange::Route { destination, source } => (format!("{base}/route/{destination}"), source.to_string()),
+            // not published, there are no lock or input status topics
+            HubChange::Lock { .. } | HubChange::InputStatus { .. } => return Ok(()),
+        };
+        self.client.publish(&topic, payload.as_bytes(), true)
+    }
+    fn handle_command(&mut self, publish: &MqttPublish) -> anyhow::Result<()> {
+        let path = publish
+            .topic
+            .strip_prefix(&self.base_topic)
+            .and_then(|topic| topic.strip_suffix("/set"))
+            .ok_or_else(|| anyhow!("Unexpected topic"))?;
+        let payload = std::str::from_utf8(&publish.payload)?.trim();
+        let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+
+        match parts.as_slice() {
+            ["route", destination] => {
+                let destination_id = self.check_id(destination.parse()?, self.hub.output_count(), "Destination")?;
+                let source_id = self.check_id(payload.parse()?, self.hub.input_count(), "Source")?;
+                self.hub.set_routes(vec![VideoHubRoute { destination_id, source_id }])
+            }
+            ["label", "input", index] => {
+                let index = self.check_id(index.parse()?, self.hub.input_count(), "Input")?;
+                self.hub.set_label(VideoHubLabelType::Input, index, payload)
+            }
+            ["label", "output", index] => {
+                let index = self.check_id(index.parse()?, self.hub.output_count(), "Output")?;
+                self.hub.set_label(VideoHubLabelType::Output, index, payload)
+            }
+            _ => Err(anyhow!("Unknown command topic")),
+        }
+    }
+    fn check_id(&self, id: usize, count: usize, kind: &str) -> anyhow::Result<usize> {
+        match id < count {
+            true => Ok(id),
+            false => Err(anyhow!("{} {} is out of range, the router has {}", kind, id, count)),
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use anyhow::anyhow;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use crate::debug_println;
+use crate::video_hub::{HubChange, VideoHub, VideoHubLabelType, VideoHubRoute};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    String(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+impl OscArg {
+    fn as_index(&self) -> anyhow::Result<usize> {
+        let index = match self {
+            OscArg::Int(i) => *i as i64,
+            // control surfaces such as TouchOSC send every number as a float
+            OscArg::Float(f) => f.round() as i64,
+            OscArg::String(s) => s.trim().parse()?,
+        };
+        usize::try_from(index).map_err(|_| anyhow!("Index can not be negative: {}", index))
+    }
+    fn as_string(&self) -> String {
+        match self {
+            OscArg::Int(i) => i.to_string(),
+            OscArg::Float(f) => f.to_string(),
+            OscArg::String(s) => s.clone(),
+        }
+    }
+}
+
+// OSC strings are null terminated and padded to a multiple of 4 bytes
+fn encode_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(s.as_bytes());
+    buf.push(0);
+    while !buf.len().is_multiple_of(4) {
+        buf.push(0);
+    }
+}
+
+fn decode_string(buf: &[u8], pos: &mut usize) -> anyhow::Result<String> {
+    let rest = buf.get(*pos..).ok_or_else(|| anyhow!("OSC packet truncated"))?;
+    let len = rest
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or_else(|| anyhow!("OSC string is not terminated"))?;
+    let s = String::from_utf8(rest[..len].to_vec())?;
+    *pos += (len + 4) & !3;
+    Ok(s)
+}
+
+fn decode_u32(buf: &[u8], pos: &mut usize) -> anyhow::Result<u32> {
+    let bytes = buf
+        .get(*pos..*pos + 4)
+        .ok_or_else(|| anyhow!("OSC packet truncated"))?;
+    *pos += 4;
+    Ok(u32::from_be_bytes(bytes.try_into()?))
+}
+
+impl OscMessage {
+    pub fn new(address: &str, args: Vec<OscArg>) -> OscMessage {
+        OscMessage {
+            address: address.to_string(),
+            args,
+        }
+    }
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        encode_string(&mut buf, &self.address);
+
+        let mut type_tags = ",".to_string();
+        for arg in &self.args {
+            type_tags.push(match arg {
+                OscArg::Int(_) => 'i',
+                OscArg::Float(_) => 'f',
+                OscArg::String(_) => 's',
+            });
+        }
+        encode_string(&mut buf, &type_tags);
+
+        for arg in &self.args {
+            match arg {
+                OscArg::Int(i) => buf.extend_from_slice(&i.to_be_bytes()),
+                OscArg::Float(f) => buf.extend_from_slice(&f.to_be_bytes()),
+                OscArg::String(s) => encode_string(&mut buf, s),
+            }
+        }
+        buf
+    }
+    /// Decodes a packet into its messages, bundles are flattened and their time tags ignored.
+    pub fn decode(packet: &[u8]) -> anyhow::Result<Vec<OscMessage>> {
+        let mut pos = 0;
+        let address = decode_string(packet, &mut pos)?;
+
+        if address == "#bundle" {
+            // skip time tag
+            pos += 8;
+            let mut messages = Vec::new();
+            while pos < packet.len() {
+                let size = decode_u32(packet, &mut pos)? as usize;
+                let element = packet
+                    .get(pos..pos + size)
+                    .ok_or_else(|| anyhow!("OSC bundle element truncated"))?;
+                messages.extend(OscMessage::decode(element)?);
+                pos += size;
+            }
+            return Ok(messages);
+        }
+
+        // type tags are optional in OSC 1.0
+        let type_tags = if pos < packet.len() {
+            decode_string(packet, &mut pos)?
+        } else {
+            ",".to_string()
+        };
+
+        let mut args = Vec::new();
+        for tag in type_tags.chars().skip(1) {
+            match tag {
+                'i' => args.push(OscArg::Int(decode_u32(packet, &mut pos)? as i32)),
+                'f' => args.push(OscArg::Float(f32::from_bits(decode_u32(packet, &mut pos)?))),
+                's' => args.push(OscArg::String(decode_string(packet, &mut pos)?)),
+                'T' | 'F' | 'N' | 'I' => continue,
+                _ => return Err(anyhow!("Unsupported OSC type tag: {}", tag)),
+            }
+        }
+        Ok(vec![OscMessage { address, args }])
+    }
+}
+
+#[test]
+fn test_osc_message_encode() {
+    let msg = OscMessage::new("/videohub/route", vec![OscArg::Int(3), OscArg::Int(5)]);
+    assert_eq!(
+        msg.encode(),
+        b"/videohub/route\0,ii\0\0\0\0\x03\0\0\0\x05".to_vec()
+    );
+}
+
+#[test]
+fn test_osc_message_round_trip() {
+    let msg = OscMessage::new(
+        "/videohub/label/input",
+        vec![OscArg::Int(2), OscArg::String("Cam 2".to_string()), OscArg::Float(0.5)],
+    );
+    let decoded = OscMessage::decode(&msg.encode()).expect("Failed to decode message");
+    assert_eq!(decoded, vec![msg]);
+}
+
+#[test]
+fn test_osc_bundle_decode() {
+    let first = OscMessage::new("/videohub/route", vec![OscArg::Int(0), OscArg::Int(1)]);
+    let second = OscMessage::new("/videohub/route", vec![OscArg::Int(1), OscArg::Int(0)]);
+
+    let mut bundle = Vec::new();
+    encode_string(&mut bundle, "#bundle");
+    bundle.extend_from_slice(&1u64.to_be_bytes());
+    for msg in [&first, &second] {
+        let encoded = msg.encode();
+        bundle.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+        bundle.extend(encoded);
+    }
+
+    let decoded = OscMessage::decode(&bundle).expect("Failed to decode bundle");
+    assert_eq!(decoded, vec![first, second]);
+}
+
+/// Maps OSC messages received over UDP onto a Videohub and sends feedback
+/// when the router state changes.
+///
+/// Addresses, all ids are 0-based:
+/// - `/videohub/route <dest> <src>`
+/// - `/videohub/label/input <n> <name>` and `/videohub/label/output <n> <name>`
+/// - `/videohub/refresh` sends the full state as feedback
+///
+/// Feedback uses the same addresses as the commands.
+pub struct OscBridge {
+    hub: VideoHub,
+    socket: UdpSocket,
+    feedback: Vec<SocketAddr>,
+}
+
+impl OscBridge {
+    pub fn new(hub: VideoHub, socket: UdpSocket, feedback: Vec<SocketAddr>) -> OscBridge {
+        OscBridge {
+            hub,
+            socket,
+            feedback,
+        }
+    }
+    pub fn hub(&self) -> &VideoHub {
+        &self.hub
+    }
+    /// Runs one iteration of the bridge, waiting up to `timeout` for each side.
+    pub fn step(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        self.socket.set_read_timeout(Some(timeout))?;
+        let mut buf = [0u8; 4096];
+        match self.socket.recv_from(&mut buf) {
+            Ok((size, from)) => match OscMessage::decode(&buf[..size]) {
+                Ok(messages) => {
+                    for msg in messages {
+                        if let Err(e) = self.handle_message(&msg) {
+                            eprintln!("Failed to apply OSC message {} from {}: {}", msg.address, from, e);
+                        }
+                    }
+                }
+                Err(e) => debug_println!("Failed to decode OSC packet from {}: {}", from, e),
+            },
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        for change in self.hub.poll(timeout)? {
+            if let Some(msg) = change_message(&change) {
+                self.send_feedback(&msg)?;
+            }
+        }
+
+        Ok(())
+    }
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        loop {
+            self.step(Duration::from_millis(50))?;
+        }
+    }
+    fn send_feedback(&self, msg: &OscMessage) -> anyhow::Result<()> {
+        let packet = msg.encode();
+        for addr in &self.feedback {
+            self.socket.send_to(&packet, addr)?;
+        }
+        Ok(())
+    }
+    fn send_state(&self) -> anyhow::Result<()> {
+        let labels = self.hub.input_labels().iter().enumerate().map(|(index, name)| HubChange::InputLabel {
+            index,
+            name: name.clone(),
+        });
+        let output_labels = self.hub.output_labels().iter().enumerate().map(|(index, name)| HubChange::OutputLabel {
+            index,
+            name: name.clone(),
+        });
+        let routes = self.hub.video_routes().iter().enumerate().map(|(destination, source)| HubChange::Route {
+            destination,
+            source: *source,
+        });
+
+        for msg in labels.chain(output_labels).chain(routes).filter_map(|change| change_message(&change)) {
+            self.send_feedback(&msg)?;
+        }
+        Ok(())
+    }
+    fn handle_message(&mut self, msg: &OscMessage) -> anyhow::Result<()> {
+        match (msg.address.as_str(), msg.args.as_slice()) {
+            ("/videohub/route", [destination, source]) => {
+                let destination_id = self.check_id(destination.as_index()?, self.hub.output_count(), "Destination")?;
+                let source_id = self.check_id(source.as_index()?, self.hub.input_count(), "Source")?;
+                self.hub.set_routes(vec![VideoHubRoute { destination_id, source_id }])
+            }
+            ("/videohub/label/input", [index, name]) => {
+                let index = self.check_id(index.as_index()?, self.hub.input_count(), "Input")?;
+                self.hub.set_label(VideoHubLabelType::Input, index, &name.as_string())
+            }
+            ("/videohub/label/output", [index, name]) => {
+                let index = self.check_id(index.as_index()?, self.hub.output_count(), "Output")?;
+                self.hub.set_label(VideoHubLabelType::Output, index, &name.as_string())
+            }
+            ("/videohub/refresh", _) => self.send_state(),
+            _ => Err(anyhow!("Unknown address or arguments")),
+        }
+    }
+    fn check_id(&self, id: usize, count: usize, kind: &str) -> anyhow::Result<usize> {
+        match id < count {
+            true => Ok(id),
+            false => Err(anyhow!("{} {} is out of range, the router has {}", kind, id, count)),
+        }
+    }
+}
+
+fn change_message(change: &HubChange) -> Option<OscMessage> {
+    match change {
+        HubChange::InputLabel { index, name } => Some(OscMessage::new(
+            "/videohub/label/input",
+            vec![OscArg::Int(*index as i32), OscArg::String(name.clone())],
+        )),
+        HubChange::OutputLabel { index, name } => Some(OscMessage::new(
+            "/videohub/label/output",
+            vec![OscArg::Int(*index as i32), OscArg::String(name.clone())],
+        )),
+        HubChange::Route { destination, source } => Some(OscMessage::new(
+            "/videohub/route",
+            vec![OscArg::Int(*destination as i32), OscArg::Int(*source as i32)],
+        )),
+        // not part of the OSC address space
+        HubChange::Lock { .. } | HubChange::InputStatus { .. } => None,
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::video_hub::{DumpDiff, DumpFormat, IdSelection, ImportFilter, VideoHub, VideoHubDump, VideoHubRoute};
+
+/// A named set of routes that is applied in one go, e.g. "Rehearsal" or "Live".
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Salvo {
+    pub name: String,
+    pub routes: Vec<VideoHubRoute>,
+}
+
+/// The salvos of a show, stored as JSON, YAML or TOML depending on the file extension.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SalvoFile {
+    /// Number of the first input and output used by the routes, 0 or 1
+    #[serde(default)]
+    pub id_base: usize,
+    pub salvos: Vec<Salvo>,
+}
+
+impl SalvoFile {
+    pub fn load(path: &Path) -> anyhow::Result<SalvoFile> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let file = match DumpFormat::from_path(path) {
+            Some(DumpFormat::Yaml) => serde_yaml::from_str(&text)?,
+            Some(DumpFormat::Toml) => toml::from_str(&text)?,
+            _ => serde_json::from_str(&text)?,
+        };
+        Ok(file)
+    }
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let text = match DumpFormat::from_path(path) {
+            Some(DumpFormat::Yaml) => serde_yaml::to_string(self)?,
+            Some(DumpFormat::Toml) => toml::to_string_pretty(self)?,
+            _ => serde_json::to_string_pretty(self)?,
+        };
+        fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+    }
+    /// Finds a salvo by name, ignoring case.
+    pub fn get(&self, name: &str) -> anyhow::Result<&Salvo> {
+        self.salvos
+            .iter()
+            .find(|salvo| salvo.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| anyhow!("No salvo named {}", name))
+    }
+    /// Adds a salvo, replacing one with the same name.
+    pub fn insert(&mut self, salvo: Salvo) {
+        match self.salvos.iter_mut().find(|existing| existing.name.eq_ignore_ascii_case(&salvo.name)) {
+            Some(existing) => *existing = salvo,
+            None => self.salvos.push(salvo),
+        }
+    }
+}
+
+// The routes of a salvo as a dump, so it is checked and compared like an import
+fn salvo_dump(salvo: &Salvo, id_base: usize) -> VideoHubDump {
+    VideoHubDump {
+        format_version: crate::video_hub::DUMP_FORMAT_VERSION,
+        time: 0,
+        id_base,
+        name: salvo.name.clone(),
+        sources: Vec::new(),
+        destinations: Vec::new(),
+        routes: salvo.routes.clone(),
+    }
+}
+
+/// Checks that the router has every input and output `salvo` routes.
+pub fn check_salvo(hub: &VideoHub, salvo: &Salvo, id_base: usize) -> anyhow::Result<()> {
+    for route in &salvo.routes {
+        if route.destination_id < id_base
+            || route.destination_id >= hub.output_count() + id_base
+            || route.source_id < id_base
+            || route.source_id >= hub.input_count() + id_base
+        {
+            return Err(anyhow!(
+                "Salvo {} routes input {} to output {}, which the router does not have",
+                salvo.name,
+                route.source_id,
+                route.destination_id
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The route changes applying `salvo` would make, with 0-based ids.
+pub fn salvo_diff(hub: &VideoHub, salvo: &Salvo, id_base: usize) -> anyhow::Result<DumpDiff> {
+    check_salvo(hub, salvo, id_base)?;
+    hub.diff(salvo_dump(salvo, id_base), &ImportFilter::default())
+}
+
+/// Applies the routes of `salvo` that differ from the current routing as a
+/// single routing block and returns what changed, with 0-based ids.
+pub fn apply_salvo(hub: &mut VideoHub, salvo: &Salvo, id_base: usize) -> anyhow::Result<DumpDiff> {
+    let diff = salvo_diff(hub, salvo, id_base)?;
+    if diff.routes.is_empty() {
+        return Ok(diff);
+    }
+    let routes = diff
+        .routes
+        .iter()
+        .map(|route| VideoHubRoute {
+            destination_id: route.destination_id,
+            source_id: route.to,
+        })
+        .collect();
+    hub.set_routes(routes)
+        .with_context(|| format!("Failed to apply salvo {}", salvo.name))?;
+    Ok(diff)
+}
+
+/// Records the current routes of the selected destinations, which use 0-based
+/// ids, as a salvo numbered from `id_base`.
+pub fn capture_salvo(hub: &VideoHub, name: &str, destinations: &IdSelection, id_base: usize) -> Salvo {
+    Salvo {
+        name: name.to_string(),
+        routes: hub
+            .video_routes()
+            .iter()
+            .enumerate()
+            .filter(|(destination, _)| destinations.contains(*destination))
+            .map(|(destination, source)| VideoHubRoute {
+                destination_id: destination + id_base,
+                source_id: source + id_base,
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn test_salvo_file() {
+    let mut file: SalvoFile = serde_json::from_str(
+        r#"{"salvos": [{"name": "Live", "routes": [{"destinationId": 0, "sourceId": 3}]}]}"#,
+    )
+    .expect("Failed to parse salvo file");
+    assert_eq!(file.id_base, 0);
+    assert_eq!(file.get("live").expect("Salvo not found").routes.len(), 1);
+    assert!(file.get("Strike").is_err());
+
+    file.insert(Salvo {
+        name: "LIVE".to_string(),
+        routes: Vec::new(),
+    });
+    file.insert(Salvo {
+        name: "Strike".to_string(),
+        routes: Vec::new(),
+    });
+    assert_eq!(file.salvos.len(), 2);
+    assert_eq!(file.salvos[0].name, "LIVE");
+    assert!(file.salvos[0].routes.is_empty());
+}
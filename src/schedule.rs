@@ -0,0 +1,669 @@
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::SocketAddrV4;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::debug_println;
+use crate::audit::AuditLog;
+use crate::salvo::{apply_salvo, check_salvo, SalvoFile};
+use crate::video_hub::{DumpFormat, VideoHub, VideoHubLabel, VideoHubLabelType};
+use crate::watch::{format_time, parse_time};
+
+/// Events that were due more than this long ago count as missed.
+pub const MISSED_AFTER: u64 = 60;
+
+/// How often an event the Videohub refuses is tried before it is given up.
+pub const EVENT_ATTEMPTS: u32 = 3;
+
+/// What to do with events that were due while hub_util or the Videohub was down.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MissedPolicy {
+    /// Drop missed events
+    #[default]
+    Skip,
+    /// Run every missed event in order
+    RunAll,
+    /// Run only the last missed occurrence of each event
+    RunLatest,
+}
+
+impl FromStr for MissedPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<MissedPolicy> {
+        match s {
+            "skip" => Ok(MissedPolicy::Skip),
+            "run-all" => Ok(MissedPolicy::RunAll),
+            "run-latest" => Ok(MissedPolicy::RunLatest),
+            _ => Err(anyhow!("Unknown missed event policy {}, expected skip, run-all or run-latest", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    #[serde(alias = "monday")]
+    Mon,
+    #[serde(alias = "tuesday")]
+    Tue,
+    #[serde(alias = "wednesday")]
+    Wed,
+    #[serde(alias = "thursday")]
+    Thu,
+    #[serde(alias = "friday")]
+    Fri,
+    #[serde(alias = "saturday")]
+    Sat,
+    #[serde(alias = "sunday")]
+    Sun,
+}
+
+impl Weekday {
+    const ALL: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+
+    // 1970-01-01 was a Thursday
+    fn from_days(days: u64) -> Weekday {
+        Weekday::ALL[((days + 3) % 7) as usize]
+    }
+}
+
+/// When an event runs, in UTC: every day at `HH:MM[:SS]` or once at
+/// `YYYY-MM-DDTHH:MM[:SS]Z`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub enum EventTime {
+    /// Seconds since midnight
+    Daily(u64),
+    /// Seconds since the Unix epoch
+    Once(u64),
+}
+
+fn parse_clock(s: &str) -> anyhow::Result<u64> {
+    let parts = s
+        .split(':')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<Vec<u64>, _>>()
+        .with_context(|| format!("Invalid time {}", s))?;
+    match parts[..] {
+        [hours, minutes] if hours < 24 && minutes < 60 => Ok(hours * 3600 + minutes * 60),
+        [hours, minutes, seconds] if hours < 24 && minutes < 60 && seconds < 60 => {
+            Ok(hours * 3600 + minutes * 60 + seconds)
+        }
+        _ => Err(anyhow!("Invalid time {}, expected HH:MM or HH:MM:SS", s)),
+    }
+}
+
+impl FromStr for EventTime {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<EventTime> {
+        match s.contains(['T', ' ']) {
+            true => Ok(EventTime::Once(parse_time(s)?.as_secs())),
+            false => Ok(EventTime::Daily(parse_clock(s)?)),
+        }
+    }
+}
+
+impl TryFrom<String> for EventTime {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<EventTime> {
+        s.parse()
+    }
+}
+
+impl From<EventTime> for String {
+    fn from(time: EventTime) -> String {
+        time.to_string()
+    }
+}
+
+impl fmt::Display for EventTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventTime::Daily(seconds) => {
+                write!(f, "{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+            }
+            EventTime::Once(seconds) => {
+                write!(f, "{}", format_time(Duration::from_secs(*seconds)).replace(".000Z", "Z"))
+            }
+        }
+    }
+}
+
+/// A timed change: routes, a salvo and labels, applied in that order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleEvent {
+    #[serde(default)]
+    pub name: String,
+    pub at: EventTime,
+    /// Days a daily event runs on, every day if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
+    /// Routes as destination=source like the `route` command
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<String>,
+    /// Name of a salvo in the salvo file of the schedule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salvo: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub input_labels: Vec<VideoHubLabel>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output_labels: Vec<VideoHubLabel>,
+}
+
+impl ScheduleEvent {
+    /// Times in seconds since the Unix epoch at which the event runs, after
+    /// `after` and up to and including `until`.
+    pub fn occurrences(&self, after: u64, until: u64) -> Vec<u64> {
+        match self.at {
+            EventTime::Once(time) => [time].into_iter().filter(|time| *time > after && *time <= until).collect(),
+            EventTime::Daily(seconds) => (after / 86400..=until / 86400)
+                .filter(|day| self.days.is_empty() || self.days.contains(&Weekday::from_days(*day)))
+                .map(|day| day * 86400 + seconds)
+                .filter(|time| *time > after && *time <= until)
+                .collect(),
+        }
+    }
+    fn label(&self, index: usize) -> String {
+        match self.name.is_empty() {
+            true => format!("Event {}", index + 1),
+            false => self.name.clone(),
+        }
+    }
+}
+
+/// Timed changes stored as JSON, YAML or TOML depending on the file extension.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    /// Number of the first input and output used by the events, 0 or 1
+    #[serde(default)]
+    pub id_base: usize,
+    /// Salvo file used by the events, relative to the schedule file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salvo_file: Option<PathBuf>,
+    #[serde(default)]
+    pub missed: MissedPolicy,
+    /// Missed events due longer ago than this many seconds are always skipped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_late: Option<u64>,
+    pub events: Vec<ScheduleEvent>,
+}
+
+impl Schedule {
+    pub fn load(path: &Path) -> anyhow::Result<Schedule> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut schedule: Schedule = match DumpFormat::from_path(path) {
+            Some(DumpFormat::Yaml) => serde_yaml::from_str(&text)?,
+            Some(DumpFormat::Toml) => toml::from_str(&text)?,
+            _ => serde_json::from_str(&text)?,
+        };
+        if let (Some(salvo_file), Some(dir)) = (&schedule.salvo_file, path.parent()) {
+            schedule.salvo_file = Some(dir.join(salvo_file));
+        }
+        Ok(schedule)
+    }
+    /// Checks that every event changes something, only uses known salvos and
+    /// that its routes and labels are well formed.
+    pub fn validate(&self, salvos: Option<&SalvoFile>) -> anyhow::Result<()> {
+        for (index, event) in self.events.iter().enumerate() {
+            let label = event.label(index);
+            if event.routes.is_empty()
+                && event.salvo.is_none()
+                && event.input_labels.is_empty()
+                && event.output_labels.is_empty()
+            {
+                return Err(anyhow!("{} does not change anything", label));
+            }
+            if matches!(event.at, EventTime::Once(_)) && !event.days.is_empty() {
+                return Err(anyhow!("{} runs once, days only apply to daily events", label));
+            }
+            if let Some(salvo) = &event.salvo {
+                salvos
+                    .ok_or_else(|| anyhow!("{} uses salvo {} but the schedule has no salvoFile", label, salvo))?
+                    .get(salvo)
+                    .with_context(|| format!("{} uses an unknown salvo", label))?;
+            }
+            if let Some(route) = event.routes.iter().find(|route| !route.contains('=')) {
+                return Err(anyhow!("{} has invalid route {}, expected destination=source", label, route));
+            }
+            for labels in [&event.input_labels, &event.output_labels] {
+                zero_based_labels(labels, self.id_base).with_context(|| format!("{} has an invalid label id", label))?;
+            }
+        }
+        Ok(())
+    }
+    /// Checks the ids of every event and salvo against the size of `hub`.
+    pub fn check_router(&self, hub: &VideoHub, salvos: Option<&SalvoFile>) -> anyhow::Result<()> {
+        for (index, event) in self.events.iter().enumerate() {
+            let label = event.label(index);
+            hub.check_route_ids(&event.routes, self.id_base)
+                .with_context(|| format!("{} routes outside of the router", label))?;
+            for (labels, count, kind) in [
+                (&event.input_labels, hub.input_count(), "input"),
+                (&event.output_labels, hub.output_count(), "output"),
+            ] {
+                if let Some(id) = labels.iter().map(|label| label.id).find(|id| *id >= count + self.id_base) {
+                    return Err(anyhow!("{} labels {} {}, the router has {}", label, kind, id, count));
+                }
+            }
+            if let (Some(name), Some(salvos)) = (&event.salvo, salvos) {
+                check_salvo(hub, salvos.get(name)?, salvos.id_base).with_context(|| format!("{} uses an invalid salvo", label))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An event occurrence that is due.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DueEvent {
+    /// Index into the events of the schedule
+    pub index: usize,
+    /// When it was due, in seconds since the Unix epoch
+    pub time: u64,
+    pub missed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventOutcome {
+    Applied,
+    Skipped,
+    /// Failed and tried again after the retry interval
+    Retrying(String),
+    /// Failed [`EVENT_ATTEMPTS`] times and was given up
+    Failed(String),
+}
+
+/// Result of an event occurrence, printed as a log line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventLog {
+    pub time: u64,
+    pub name: String,
+    pub missed: bool,
+    pub outcome: EventOutcome,
+}
+
+impl fmt::Display for EventLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", format_time(Duration::from_secs(self.time)), self.name)?;
+        if self.missed {
+            write!(f, " (missed)")?;
+        }
+        match &self.outcome {
+            EventOutcome::Applied => write!(f, ": applied"),
+            EventOutcome::Skipped => write!(f, ": skipped"),
+            EventOutcome::Retrying(e) => write!(f, ": failed, retrying, {}", e),
+            EventOutcome::Failed(e) => write!(f, ": failed, {}", e),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct SchedulerState {
+    last_run: u64,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    handled: BTreeSet<(u64, usize)>,
+}
+
+// An event occurrence that failed and is tried again
+#[derive(Debug, Clone, Copy)]
+struct Retry {
+    attempts: u32,
+    last_attempt: Instant,
+}
+
+/// Keeps a session with a Videohub open, reconnecting when it drops, and applies
+/// the events of a schedule when they are due.
+pub struct Scheduler {
+    addr: SocketAddrV4,
+    schedule: Schedule,
+    salvos: Option<SalvoFile>,
+    hub: Option<VideoHub>,
+    last_attempt: Option<Instant>,
+    state_path: Option<PathBuf>,
+    // events up to this time have been handled, in seconds since the Unix epoch
+    last_run: u64,
+    // occurrences after last_run that were applied, skipped or given up, as (time, index)
+    handled: BTreeSet<(u64, usize)>,
+    retries: BTreeMap<(u64, usize), Retry>,
+    /// Time between reconnects and between attempts of a failed event
+    pub retry_interval: Duration,
+    /// Audit log every session with the Videohub appends to
+    pub audit_log: Option<PathBuf>,
+}
+
+impl Scheduler {
+    /// Creates a scheduler that handles events due after `now`, or after the
+    /// time recorded in `state_path` by a previous run.
+    pub fn new(addr: SocketAddrV4, schedule: Schedule, state_path: Option<PathBuf>, now: u64) -> anyhow::Result<Scheduler> {
+        let salvos = match &schedule.salvo_file {
+            Some(path) => Some(SalvoFile::load(path)?),
+            None => None,
+        };
+        schedule.validate(salvos.as_ref())?;
+
+        let state = match &state_path {
+            Some(path) if path.exists() => {
+                let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+                serde_json::from_str::<SchedulerState>(&text)?
+            }
+            _ => SchedulerState { last_run: now, ..Default::default() },
+        };
+        Ok(Scheduler {
+            addr,
+            schedule,
+            salvos,
+            hub: None,
+            last_attempt: None,
+            state_path,
+            last_run: state.last_run.min(now),
+            handled: state.handled,
+            retries: BTreeMap::new(),
+            retry_interval: Duration::from_secs(5),
+            audit_log: None,
+        })
+    }
+    pub fn is_connected(&self) -> bool {
+        self.hub.is_some()
+    }
+    pub fn hub(&self) -> Option<&VideoHub> {
+        self.hub.as_ref()
+    }
+    /// Event occurrences due after the last run and up to `now`, in order,
+    /// after applying the missed event policy.
+    pub fn due(&self, now: u64) -> Vec<DueEvent> {
+        let mut due: Vec<DueEvent> = Vec::new();
+        for (index, event) in self.schedule.events.iter().enumerate() {
+            let occurrences = event.occurrences(self.last_run, now);
+            let missed_count = occurrences.iter().filter(|time| *time + MISSED_AFTER < now).count();
+            for (n, time) in occurrences.into_iter().enumerate() {
+                let missed = n < missed_count;
+                let skip = missed
+                    && match self.schedule.missed {
+                        MissedPolicy::Skip => true,
+                        MissedPolicy::RunAll => false,
+                        MissedPolicy::RunLatest => n + 1 < missed_count,
+                    };
+                let too_late = missed && self.schedule.max_late.is_some_and(|max_late| time + max_late < now);
+                if !skip && !too_late && !self.handled.contains(&(time, index)) {
+                    due.push(DueEvent { index, time, missed });
+                }
+            }
+        }
+        due.sort_by_key(|event| (event.time, event.index));
+        due
+    }
+    /// Runs one iteration: keeps the Videohub session alive and applies the
+    /// events due up to `now`. Nothing is applied while the Videohub is
+    /// unreachable, events due meanwhile are handled as missed once it is back.
+    /// An event that fails is tried again after `retry_interval`, at most
+    /// [`EVENT_ATTEMPTS`] times, without holding up the events after it.
+    ///
+    /// Returns an error if the schedule uses inputs or outputs the router does not have.
+    pub fn step(&mut self, now: u64, timeout: Duration) -> anyhow::Result<Vec<EventLog>> {
+        if self.hub.is_none() {
+            self.connect()?;
+        }
+        let Some(hub) = &mut self.hub else {
+            std::thread::sleep(timeout);
+            return Ok(Vec::new());
+        };
+        if let Err(e) = hub.poll(timeout) {
+            eprintln!("Lost connection to VideoHub at {}: {}", self.addr, e);
+            self.hub = None;
+            return Ok(Vec::new());
+        }
+
+        let due = self.due(now);
+        let mut logs = Vec::new();
+        for (index, event) in self.schedule.events.iter().enumerate() {
+            for time in event.occurrences(self.last_run, now) {
+                let key = (time, index);
+                if !self.handled.contains(&key) && !due.iter().any(|due| (due.time, due.index) == key) {
+                    self.handled.insert(key);
+                    logs.push(EventLog {
+                        time,
+                        name: event.label(index),
+                        missed: true,
+                        outcome: EventOutcome::Skipped,
+                    });
+                }
+            }
+        }
+
+        let Some(hub) = &mut self.hub else {
+            return Ok(Vec::new());
+        };
+        let mut connection_lost = false;
+        for due in &due {
+            let key = (due.time, due.index);
+            let retry = self.retries.get(&key).copied();
+            if connection_lost || retry.is_some_and(|retry| retry.last_attempt.elapsed() < self.retry_interval) {
+                continue;
+            }
+            let event = &self.schedule.events[due.index];
+            let outcome = match apply_event(hub, event, self.salvos.as_ref(), self.schedule.id_base) {
+                Ok(()) => EventOutcome::Applied,
+                // the session is reopened and the event tried again, as if it was due while disconnected
+                Err(e) if e.chain().any(|cause| cause.is::<io::Error>()) => {
+                    connection_lost = true;
+                    EventOutcome::Retrying(format!("{:#}", e))
+                }
+                Err(e) => {
+                    let attempts = retry.map_or(1, |retry| retry.attempts + 1);
+                    self.retries.insert(key, Retry { attempts, last_attempt: Instant::now() });
+                    match attempts < EVENT_ATTEMPTS {
+                        true => EventOutcome::Retrying(format!("{:#}", e)),
+                        false => EventOutcome::Failed(format!("{:#}", e)),
+                    }
+                }
+            };
+            if !matches!(outcome, EventOutcome::Retrying(_)) {
+                self.handled.insert(key);
+                self.retries.remove(&key);
+            }
+            logs.push(EventLog {
+                time: due.time,
+                name: event.label(due.index),
+                missed: due.missed,
+                outcome,
+            });
+        }
+        if connection_lost {
+            eprintln!("Lost connection to VideoHub at {}", self.addr);
+            self.hub = None;
+        }
+        logs.sort_by_key(|log| log.time);
+
+        // stay before the first occurrence that is not handled yet, so it is due again next time
+        let pending = due.iter().map(|due| (due.time, due.index)).find(|key| !self.handled.contains(key));
+        let last_run = pending.map_or(now, |(time, _)| time - 1).max(self.last_run);
+        if last_run != self.last_run || !logs.is_empty() {
+            self.last_run = last_run;
+            self.handled.retain(|(time, _)| *time > last_run);
+            self.retries.retain(|(time, _), _| *time > last_run);
+            self.save_state()?;
+        }
+        Ok(logs)
+    }
+    /// Runs the schedule with the system clock, printing a line for every event.
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        loop {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            for log in self.step(now, Duration::from_millis(500))? {
+                println!("{}", log);
+            }
+        }
+    }
+    // Opens a session, failing only if the schedule does not fit the router
+    fn connect(&mut self) -> anyhow::Result<()> {
+        if self
+            .last_attempt
+            .is_some_and(|attempt| attempt.elapsed() < self.retry_interval)
+        {
+            return Ok(());
+        }
+        self.last_attempt = Some(Instant::now());
+
+        let hub = VideoHub::new(self.addr).and_then(|mut hub| {
+            if let Some(path) = &self.audit_log {
+                hub.set_audit_log(Some(AuditLog::open(path)?));
+            }
+            Ok(hub)
+        });
+        match hub {
+            Ok(hub) => {
+                self.schedule.check_router(&hub, self.salvos.as_ref())?;
+                self.hub = Some(hub);
+            }
+            Err(e) => debug_println!("Failed to connect to VideoHub at {}: {}", self.addr, e),
+        }
+        Ok(())
+    }
+    fn save_state(&self) -> anyhow::Result<()> {
+        if let Some(path) = &self.state_path {
+            let state = SchedulerState {
+                last_run: self.last_run,
+                handled: self.handled.clone(),
+            };
+            fs::write(path, serde_json::to_string(&state)?).with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn zero_based_labels(labels: &[VideoHubLabel], id_base: usize) -> anyhow::Result<Vec<VideoHubLabel>> {
+    labels
+        .iter()
+        .map(|label| {
+            Ok(VideoHubLabel {
+                id: label.id.checked_sub(id_base).ok_or_else(|| anyhow!("Label ids start at {}", id_base))?,
+                name: label.name.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Applies the routes, salvo and labels of an event.
+pub fn apply_event(
+    hub: &mut VideoHub,
+    event: &ScheduleEvent,
+    salvos: Option<&SalvoFile>,
+    id_base: usize,
+) -> anyhow::Result<()> {
+    if !event.routes.is_empty() {
+        let routes = hub.parse_routes(&event.routes, id_base)?;
+        hub.set_routes(routes)?;
+    }
+    if let Some(name) = &event.salvo {
+        let salvos = salvos.ok_or_else(|| anyhow!("No salvo file to take salvo {} from", name))?;
+        apply_salvo(hub, salvos.get(name)?, salvos.id_base)?;
+    }
+    for (label_type, labels) in [
+        (VideoHubLabelType::Input, &event.input_labels),
+        (VideoHubLabelType::Output, &event.output_labels),
+    ] {
+        if labels.is_empty() {
+            continue;
+        }
+        let labels = zero_based_labels(labels, id_base)?;
+        let count = match label_type {
+            VideoHubLabelType::Input => hub.input_count(),
+            VideoHubLabelType::Output => hub.output_count(),
+        };
+        if let Some(label) = labels.iter().find(|label| label.id >= count) {
+            return Err(anyhow!("Label {} is out of range", label.id + id_base));
+        }
+        hub.set_labels(label_type, labels)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_event_time() {
+    assert_eq!("06:30".parse::<EventTime>().expect("Failed to parse"), EventTime::Daily(23400));
+    assert_eq!("23:59:59".parse::<EventTime>().expect("Failed to parse"), EventTime::Daily(86399));
+    assert!("24:00".parse::<EventTime>().is_err());
+
+    let once: EventTime = "2025-03-18T18:50:54Z".parse().expect("Failed to parse");
+    assert_eq!(once, EventTime::Once(1_742_323_854));
+    assert_eq!(once.to_string(), "2025-03-18T18:50:54Z");
+    assert_eq!("2000-02-29 00:00Z".parse::<EventTime>().expect("Failed to parse"), EventTime::Once(951_782_400));
+    assert!("2025-03-18T18:50".parse::<EventTime>().is_err());
+}
+
+#[test]
+fn test_daily_occurrences() {
+    // 2025-03-17 was a Monday
+    let monday = 1_742_169_600;
+    let event = ScheduleEvent {
+        name: String::new(),
+        at: EventTime::Daily(6 * 3600),
+        days: vec![Weekday::Mon, Weekday::Wed],
+        routes: vec!["0=1".to_string()],
+        salvo: None,
+        input_labels: Vec::new(),
+        output_labels: Vec::new(),
+    };
+    assert_eq!(
+        event.occurrences(monday, monday + 7 * 86400),
+        vec![monday + 6 * 3600, monday + 2 * 86400 + 6 * 3600]
+    );
+    assert!(event.occurrences(monday + 6 * 3600, monday + 86400).is_empty());
+}
+
+#[test]
+fn test_missed_policy() {
+    let event = |name: &str, at: &str| ScheduleEvent {
+        name: name.to_string(),
+        at: at.parse().expect("Failed to parse"),
+        days: Vec::new(),
+        routes: vec!["0=1".to_string()],
+        salvo: None,
+        input_labels: Vec::new(),
+        output_labels: Vec::new(),
+    };
+    let mut schedule = Schedule {
+        events: vec![event("Daily", "06:00"), event("Once", "1970-01-03T12:00Z")],
+        ..Default::default()
+    };
+    let scheduler = |schedule: &Schedule| {
+        Scheduler::new("127.0.0.1:1".parse().expect("Failed to parse address"), schedule.clone(), None, 0)
+            .expect("Failed to create scheduler")
+    };
+    // three days later, on the 4th at 06:00:30
+    let now = 3 * 86400 + 6 * 3600 + 30;
+    let due = |scheduler: &Scheduler| scheduler.due(now).iter().map(|due| (due.index, due.time, due.missed)).collect::<Vec<_>>();
+
+    assert_eq!(due(&scheduler(&schedule)), vec![(0, now - 30, false)]);
+
+    schedule.missed = MissedPolicy::RunLatest;
+    assert_eq!(
+        due(&scheduler(&schedule)),
+        vec![(0, 2 * 86400 + 6 * 3600, true), (1, 2 * 86400 + 12 * 3600, true), (0, now - 30, false)]
+    );
+    schedule.missed = MissedPolicy::RunAll;
+    assert_eq!(due(&scheduler(&schedule)).len(), 5);
+    schedule.max_late = Some(86400);
+    assert_eq!(due(&scheduler(&schedule)).len(), 2);
+}
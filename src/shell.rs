@@ -0,0 +1,414 @@
+use anyhow::anyhow;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{
+    Cmd, ConditionalEventHandler, Context, Editor, Event, EventContext, EventHandler, ExternalPrinter, Helper, KeyCode,
+    KeyEvent, Modifiers, RepeatCount,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::show::{render_table, routing_entries};
+use crate::video_hub::{DumpFormat, VideoHub, VideoHubLabelType};
+use crate::watch::describe_change;
+
+const COMMANDS: [&str; 12] = [
+    "route", "label", "lock", "unlock", "show", "dump", "undo", "redo", "history", "help", "quit", "exit",
+];
+
+const HELP: &str = "\
+route <output> <input>           route an input to an output
+route <outputs>=<inputs> ...     route several outputs, e.g. 1-8=1-8 Program=Camera 2
+label in|out                     list input or output labels
+label in|out <id> <name>         set a label
+lock <output>, unlock <output>   lock an output for this client or release it
+show                             show the routing of every output
+dump [file]                      print a JSON dump or write it to a file
+undo, redo                       revert the last route or label change or apply it again,
+                                 Ctrl-Z and Ctrl-Y on an empty line
+history                          list the changes undo goes through, the last one first
+quit                             leave the shell";
+
+/// What the shell should do after a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShellOutcome {
+    Output(String),
+    Quit,
+}
+
+/// Runs shell commands against a single Videohub connection.
+pub struct Shell {
+    hub: VideoHub,
+    id_base: usize,
+}
+
+impl Shell {
+    pub fn new(hub: VideoHub, id_base: usize) -> Shell {
+        Shell { hub, id_base }
+    }
+    pub fn hub(&self) -> &VideoHub {
+        &self.hub
+    }
+    /// Runs a single command line and returns what should be printed.
+    pub fn execute(&mut self, line: &str) -> anyhow::Result<ShellOutcome> {
+        let words = split_words(line)?;
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (command.as_str(), args),
+            None => return Ok(ShellOutcome::Output(String::new())),
+        };
+        let pending = self.hub.pending_change_count();
+        let output = match command {
+            "route" => self.route(args)?,
+            "label" => self.label(args)?,
+            "lock" | "unlock" => self.lock(args, command == "lock")?,
+            "show" => render_table(&routing_entries(&self.hub, self.id_base)),
+            "dump" => self.dump(args)?,
+            "undo" => self.undo(false)?,
+            "redo" => self.undo(true)?,
+            "history" => self.history(),
+            "help" => format!("{}\n", HELP),
+            "quit" | "exit" => return Ok(ShellOutcome::Quit),
+            _ => return Err(anyhow!("Unknown command {}, type help for a list of commands", command)),
+        };
+        // the shell prints the result itself, do not report the changes again,
+        // changes made by other clients are kept for the notifier
+        self.hub.discard_local_changes_after(pending);
+        Ok(ShellOutcome::Output(output))
+    }
+    /// Waits up to `timeout` for changes made by other clients and describes them.
+    pub fn notifications(&mut self, timeout: Duration) -> anyhow::Result<Vec<String>> {
+        let changes = self.hub.poll(timeout)?;
+        Ok(changes
+            .iter()
+            .map(|change| describe_change(&self.hub, change, self.id_base))
+            .collect())
+    }
+    /// Words offered by tab completion: commands and all labels.
+    pub fn completion_words(&self) -> Vec<String> {
+        let mut words: Vec<String> = COMMANDS.iter().chain(["in", "out"].iter()).map(|word| word.to_string()).collect();
+        words.extend(self.hub.input_labels().iter().cloned());
+        words.extend(self.hub.output_labels().iter().cloned());
+        words.sort();
+        words.dedup();
+        words
+    }
+
+    fn route(&mut self, args: &[String]) -> anyhow::Result<String> {
+        let specs = match args {
+            [destination, source] if !destination.contains('=') && !source.contains('=') => {
+                vec![format!("{}={}", destination, source)]
+            }
+            [] => return Err(anyhow!("Usage: route <output> <input> or route <outputs>=<inputs> ...")),
+            _ => args.to_vec(),
+        };
+        let routes = self.hub.parse_routes(&specs, self.id_base)?;
+        let mut destinations: Vec<usize> = Vec::new();
+        for route in &routes {
+            if !destinations.contains(&route.destination_id) {
+                destinations.push(route.destination_id);
+            }
+        }
+        self.hub.set_routes(routes)?;
+
+        let entries = routing_entries(&self.hub, self.id_base);
+        let entries: Vec<_> = destinations.iter().map(|destination| entries[*destination].clone()).collect();
+        Ok(render_table(&entries))
+    }
+    fn label(&mut self, args: &[String]) -> anyhow::Result<String> {
+        let label_type = match args.first().map(String::as_str) {
+            Some("in" | "input") => VideoHubLabelType::Input,
+            Some("out" | "output") => VideoHubLabelType::Output,
+            _ => return Err(anyhow!("Usage: label in|out [<id> <name>]")),
+        };
+        match &args[1..] {
+            [] => Ok(self
+                .hub
+                .labels(label_type)
+                .iter()
+                .enumerate()
+                .map(|(id, label)| format!("{} {}\n", id + self.id_base, label))
+                .collect()),
+            [id, name] => {
+                let ids = self.hub.resolve_ids(label_type, id, self.id_base)?;
+                let [id] = ids[..] else {
+                    return Err(anyhow!("label takes a single id"));
+                };
+                self.hub.set_label(label_type, id, name)?;
+                Ok(format!("{} {}\n", id + self.id_base, self.hub.labels(label_type)[id]))
+            }
+            _ => Err(anyhow!("Usage: label in|out [<id> <name>], quote names with spaces")),
+        }
+    }
+    fn lock(&mut self, args: &[String], lock: bool) -> anyhow::Result<String> {
+        let [spec] = args else {
+            return Err(anyhow!("Usage: lock|unlock <output>"));
+        };
+        let mut output = String::new();
+        for destination in self.hub.resolve_ids(VideoHubLabelType::Output, spec, self.id_base)? {
+            self.hub.set_lock(destination, lock)?;
+            output += &format!(
+                "{} {} {}\n",
+                destination + self.id_base,
+                self.hub.output_labels()[destination],
+                self.hub.output_locks()[destination].name()
+            );
+        }
+        Ok(output)
+    }
+    fn undo(&mut self, redo: bool) -> anyhow::Result<String> {
+        let entry = match redo {
+            false => self.hub.undo()?.map(|entry| entry.diff.reversed()),
+            true => self.hub.redo()?.map(|entry| entry.diff),
+        };
+        Ok(match entry {
+            Some(diff) => diff.with_id_base(self.id_base).to_string(),
+            None => format!("Nothing to {}\n", if redo { "redo" } else { "undo" }),
+        })
+    }
+    fn history(&self) -> String {
+        let mut output = String::new();
+        for (n, entry) in self.hub.history().iter().rev().enumerate() {
+            let external = if entry.external { " (other client)" } else { "" };
+            for line in entry.diff.clone().with_id_base(self.id_base).to_string().lines() {
+                output += &format!("{} {}{}\n", n + 1, line, external);
+            }
+        }
+        if output.is_empty() {
+            output = "No changes\n".to_string();
+        }
+        output
+    }
+    fn dump(&mut self, args: &[String]) -> anyhow::Result<String> {
+        let dump = self.hub.dump().with_id_base(self.id_base)?;
+        match args {
+            [] => Ok(format!("{}\n", dump.to_json()?)),
+            [file] => {
+                let format = DumpFormat::from_path(Path::new(file)).unwrap_or_default();
+                fs::write(file, dump.to_format(format)?)?;
+                Ok(format!("Wrote {}\n", file))
+            }
+            _ => Err(anyhow!("Usage: dump [file]")),
+        }
+    }
+}
+
+/// Splits a command line into words, double quotes group words with spaces.
+pub fn split_words(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        return Err(anyhow!("Missing closing quote"));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+// Start of the word under the cursor, a word started with a quote runs to the cursor
+fn word_start(line: &str) -> usize {
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => {
+                if !quoted {
+                    start = i;
+                }
+                quoted = !quoted;
+            }
+            c if c.is_whitespace() && !quoted => start = i + c.len_utf8(),
+            _ => {}
+        }
+    }
+    start
+}
+
+/// Completes commands and labels, labels with spaces are inserted quoted.
+pub struct ShellHelper {
+    words: Arc<Mutex<Vec<String>>>,
+}
+
+impl ShellHelper {
+    fn candidates(&self, line: &str) -> (usize, Vec<Pair>) {
+        let start = word_start(line);
+        let prefix = line[start..].trim_start_matches('"').to_lowercase();
+        let words = self.words.lock().map(|words| words.clone()).unwrap_or_default();
+        let candidates = words
+            .into_iter()
+            .filter(|word| word.to_lowercase().starts_with(&prefix))
+            .map(|word| Pair {
+                replacement: if word.contains(' ') { format!("\"{}\"", word) } else { word.clone() },
+                display: word,
+            })
+            .collect();
+        (start, candidates)
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(self.candidates(&line[..pos]))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+// Runs a command for a key pressed on an empty line, the reader picks it up
+// from `command` once the empty line is accepted
+struct ShortcutHandler {
+    command: &'static str,
+    pending: Arc<Mutex<Option<&'static str>>>,
+}
+
+impl ConditionalEventHandler for ShortcutHandler {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        if !ctx.line().is_empty() {
+            return None;
+        }
+        *self.pending.lock().ok()? = Some(self.command);
+        Some(Cmd::AcceptLine)
+    }
+}
+
+/// Runs the interactive shell until the user quits or the connection drops.
+///
+/// Lines are read on a separate thread so changes made by other clients can be
+/// printed above the prompt while waiting for input.
+pub fn run(mut shell: Shell, history: Option<PathBuf>) -> anyhow::Result<()> {
+    let words = Arc::new(Mutex::new(shell.completion_words()));
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ShellHelper { words: words.clone() }));
+    if let Some(history) = &history {
+        // there is no history on the first start
+        let _ = editor.load_history(history);
+    }
+    let shortcut = Arc::new(Mutex::new(None));
+    for (key, command) in [('z', "undo"), ('y', "redo")] {
+        let handler = ShortcutHandler {
+            command,
+            pending: shortcut.clone(),
+        };
+        editor.bind_sequence(
+            KeyEvent(KeyCode::Char(key), Modifiers::CTRL),
+            EventHandler::Conditional(Box::new(handler)),
+        );
+    }
+    // only available on a terminal, piped input prints notifications directly
+    let mut printer = editor.create_external_printer().ok();
+
+    let (line_sender, lines) = mpsc::channel::<Option<String>>();
+    let (done_sender, done) = mpsc::channel::<()>();
+    let reader = thread::spawn(move || {
+        loop {
+            let line = match editor.readline("videohub> ") {
+                Ok(line) => match shortcut.lock().ok().and_then(|mut pending| pending.take()) {
+                    Some(command) => command.to_string(),
+                    None => line,
+                },
+                Err(ReadlineError::Interrupted) => continue,
+                Err(_) => break,
+            };
+            if !line.trim().is_empty() {
+                let _ = editor.add_history_entry(line.as_str());
+                // saved right away, this thread can not be joined when the
+                // connection drops while it waits for input
+                if let Some(history) = &history {
+                    let _ = editor.save_history(history);
+                }
+            }
+            // wait for the output so it is printed before the next prompt
+            if line_sender.send(Some(line)).is_err() || done.recv().is_err() {
+                break;
+            }
+        }
+        let _ = line_sender.send(None);
+    });
+
+    let result = loop {
+        match lines.recv_timeout(Duration::from_millis(50)) {
+            Ok(Some(line)) => {
+                match shell.execute(&line) {
+                    Ok(ShellOutcome::Output(output)) => print!("{}", output),
+                    Ok(ShellOutcome::Quit) => break Ok(()),
+                    Err(e) => println!("Error: {:#}", e),
+                }
+                if let Ok(mut words) = words.lock() {
+                    *words = shell.completion_words();
+                }
+                let _ = done_sender.send(());
+            }
+            Ok(None) | Err(RecvTimeoutError::Disconnected) => break Ok(()),
+            Err(RecvTimeoutError::Timeout) => match shell.notifications(Duration::from_millis(1)) {
+                Ok(notifications) => {
+                    for notification in notifications {
+                        match &mut printer {
+                            Some(printer) => printer.print(format!("* {}", notification))?,
+                            None => println!("* {}", notification),
+                        }
+                    }
+                }
+                Err(e) => break Err(e),
+            },
+        }
+    };
+    // after a command the reader waits for `done` and then stops, while idle it
+    // is blocked in readline and ends with the process
+    drop(done_sender);
+    if result.is_ok() {
+        let _ = reader.join();
+    }
+    result
+}
+
+#[test]
+fn test_split_words() {
+    assert_eq!(
+        split_words(r#"label in 2 "Cam 2""#).expect("Failed to split"),
+        vec!["label", "in", "2", "Cam 2"]
+    );
+    assert_eq!(split_words(r#"route  "Program"=3 "#).expect("Failed to split"), vec!["route", "Program=3"]);
+    assert_eq!(split_words(r#"label in 2 """#).expect("Failed to split"), vec!["label", "in", "2", ""]);
+    assert!(split_words(r#"label in 2 "Cam 2"#).is_err());
+}
+
+#[test]
+fn test_completion_candidates() {
+    let helper = ShellHelper {
+        words: Arc::new(Mutex::new(vec!["Camera 1".to_string(), "label".to_string(), "lock".to_string()])),
+    };
+    let (start, candidates) = helper.candidates("lab");
+    assert_eq!(start, 0);
+    assert_eq!(candidates.iter().map(|pair| pair.replacement.as_str()).collect::<Vec<_>>(), vec!["label"]);
+
+    let (start, candidates) = helper.candidates("route 3 \"cam");
+    assert_eq!(start, 8);
+    assert_eq!(candidates[0].replacement, "\"Camera 1\"");
+}
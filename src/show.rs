@@ -0,0 +1,148 @@
+use serde::Serialize;
+use std::fmt::Write as _;
+
+use crate::video_hub::{LockState, VideoHub};
+
+/// The route of one destination with its labels resolved.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutingEntry {
+    pub destination: usize,
+    pub destination_label: String,
+    pub source: usize,
+    pub source_label: String,
+    pub lock: LockState,
+}
+
+/// Lists the route of every destination, numbered from `id_base`.
+pub fn routing_entries(hub: &VideoHub, id_base: usize) -> Vec<RoutingEntry> {
+    hub.video_routes()
+        .iter()
+        .enumerate()
+        .map(|(destination, source)| RoutingEntry {
+            destination: destination + id_base,
+            destination_label: hub.output_labels()[destination].clone(),
+            source: source + id_base,
+            source_label: hub.input_labels().get(*source).cloned().unwrap_or_default(),
+            lock: hub.output_locks().get(destination).copied().unwrap_or_default(),
+        })
+        .collect()
+}
+
+// `L` is locked by another client, `O` is locked by this one, as in the protocol
+fn lock_marker(lock: LockState) -> &'static str {
+    match lock {
+        LockState::Unlocked => "",
+        LockState::Owned => "O",
+        LockState::Locked => "L",
+    }
+}
+
+/// Renders one `destination <- source` line per destination with the lock marker at the end.
+pub fn render_table(entries: &[RoutingEntry]) -> String {
+    let id_width = entries
+        .iter()
+        .map(|entry| entry.destination.max(entry.source).to_string().len())
+        .max()
+        .unwrap_or(1);
+    let label_width = entries.iter().map(|entry| entry.destination_label.chars().count()).max().unwrap_or(0);
+
+    let mut table = String::new();
+    for entry in entries {
+        let line = format!(
+            "{:>id_width$} {:<label_width$} <- {:>id_width$} {} {}",
+            entry.destination,
+            entry.destination_label,
+            entry.source,
+            entry.source_label,
+            lock_marker(entry.lock),
+        );
+        let _ = writeln!(table, "{}", line.trim_end());
+    }
+    table
+}
+
+/// Renders a crosspoint grid with a row per destination and a column per source,
+/// the sources are numbered from `id_base` like the entries.
+pub fn render_grid(entries: &[RoutingEntry], input_count: usize, id_base: usize) -> String {
+    let id_width = (input_count + id_base).max(entries.len() + id_base).to_string().len();
+    let label_width = entries.iter().map(|entry| entry.destination_label.chars().count()).max().unwrap_or(0);
+
+    let mut grid = String::new();
+    // column numbers are written top to bottom to keep the grid narrow
+    for digit in 0..id_width {
+        let _ = write!(grid, "{:width$} ", "", width = id_width + label_width + 1);
+        for source in id_base..input_count + id_base {
+            let number = format!("{:>id_width$}", source);
+            grid.push(number.chars().nth(digit).unwrap_or(' '));
+        }
+        grid.push('\n');
+    }
+    for entry in entries {
+        let crosspoints: String = (id_base..input_count + id_base)
+            .map(|source| if source == entry.source { 'X' } else { '.' })
+            .collect();
+        let line = format!(
+            "{:>id_width$} {:<label_width$} {} {}",
+            entry.destination,
+            entry.destination_label,
+            crosspoints,
+            lock_marker(entry.lock)
+        );
+        let _ = writeln!(grid, "{}", line.trim_end());
+    }
+    grid
+}
+
+pub fn render_csv(entries: &[RoutingEntry]) -> anyhow::Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["destination", "destination_label", "source", "source_label", "lock"])?;
+    for entry in entries {
+        writer.write_record([
+            entry.destination.to_string(),
+            entry.destination_label.clone(),
+            entry.source.to_string(),
+            entry.source_label.clone(),
+            entry.lock.name().to_string(),
+        ])?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+#[cfg(test)]
+fn test_entries() -> Vec<RoutingEntry> {
+    vec![
+        RoutingEntry {
+            destination: 1,
+            destination_label: "Program".to_string(),
+            source: 3,
+            source_label: "Camera 3".to_string(),
+            lock: LockState::Unlocked,
+        },
+        RoutingEntry {
+            destination: 2,
+            destination_label: "Mon".to_string(),
+            source: 1,
+            source_label: "Camera 1".to_string(),
+            lock: LockState::Locked,
+        },
+    ]
+}
+
+#[test]
+fn test_render_table() {
+    assert_eq!(render_table(&test_entries()), "1 Program <- 3 Camera 3\n2 Mon     <- 1 Camera 1 L\n");
+}
+
+#[test]
+fn test_render_grid() {
+    assert_eq!(render_grid(&test_entries(), 3, 1), "          123\n1 Program ..X\n2 Mon     X.. L\n");
+}
+
+#[test]
+fn test_render_csv() {
+    assert_eq!(
+        render_csv(&test_entries()).expect("Failed to render CSV"),
+        "destination,destination_label,source,source_label,lock\n1,Program,3,Camera 3,unlocked\n2,Mon,1,Camera 1,locked\n"
+    );
+}
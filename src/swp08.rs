@@ -0,0 +1,409 @@
+use anyhow::anyhow;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use crate::debug_println;
+use crate::video_hub::{HubChange, VideoHub, VideoHubRoute};
+
+const DLE: u8 = 0x10;
+const STX: u8 = 0x02;
+const ETX: u8 = 0x03;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+
+const CROSSPOINT_INTERROGATE: u8 = 0x01;
+const CROSSPOINT_CONNECT: u8 = 0x02;
+const CROSSPOINT_TALLY: u8 = 0x03;
+const CROSSPOINT_CONNECTED: u8 = 0x04;
+const SOURCE_NAMES_REQUEST: u8 = 0x64;
+const SINGLE_SOURCE_NAME_REQUEST: u8 = 0x65;
+const DESTINATION_NAMES_REQUEST: u8 = 0x66;
+const SINGLE_DESTINATION_NAME_REQUEST: u8 = 0x67;
+const SOURCE_NAMES_RESPONSE: u8 = 0x6A;
+const DESTINATION_NAMES_RESPONSE: u8 = 0x6B;
+
+/// Wraps the command and message bytes in a DLE STX ... DLE ETX frame with
+/// byte count and checksum, doubling every DLE inside the frame.
+fn encode_frame(data: &[u8]) -> Vec<u8> {
+    let btc = data.len() as u8;
+    let sum = data.iter().fold(btc, |sum, byte| sum.wrapping_add(*byte));
+    let chk = sum.wrapping_neg();
+
+    let mut frame = vec![DLE, STX];
+    for byte in data.iter().chain([btc, chk].iter()) {
+        if *byte == DLE {
+            frame.push(DLE);
+        }
+        frame.push(*byte);
+    }
+    frame.extend_from_slice(&[DLE, ETX]);
+    frame
+}
+
+#[derive(Debug, PartialEq)]
+enum Frame {
+    Data(Vec<u8>),
+    Invalid,
+}
+
+/// Removes every complete frame from `buf`, leaving a trailing partial frame in place.
+fn decode_frames(buf: &mut Vec<u8>) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    let mut consumed = 0;
+    let mut i = 0;
+
+    while i + 1 < buf.len() {
+        if buf[i] != DLE || buf[i + 1] != STX {
+            // skip DLE ACK / DLE NAK from the controller and anything unframed
+            i += 1;
+            consumed = i;
+            continue;
+        }
+
+        let mut body = Vec::new();
+        let mut j = i + 2;
+        let mut complete = false;
+        while j + 1 < buf.len() {
+            match (buf[j], buf[j + 1]) {
+                (DLE, DLE) => {
+                    body.push(DLE);
+                    j += 2;
+                }
+                (DLE, ETX) => {
+                    j += 2;
+                    complete = true;
+                    break;
+                }
+                (byte, _) => {
+                    body.push(byte);
+                    j += 1;
+                }
+            }
+        }
+        if !complete {
+            break;
+        }
+
+        frames.push(match body.len() {
+            len if len < 3 => Frame::Invalid,
+            len => {
+                let (data, check) = body.split_at(len - 2);
+                let sum = body[..len - 1].iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+                if check[0] as usize == data.len() && sum.wrapping_add(check[1]) == 0 {
+                    Frame::Data(data.to_vec())
+                } else {
+                    Frame::Invalid
+                }
+            }
+        });
+        i = j;
+        consumed = j;
+    }
+
+    buf.drain(..consumed);
+    frames
+}
+
+#[test]
+fn test_frame_round_trip() {
+    // crosspoint connect on matrix 0, destination 16, source 2, contains a DLE
+    let data = vec![CROSSPOINT_CONNECT, 0x00, 0x00, 0x10, 0x02];
+    let mut buf = encode_frame(&data);
+    assert_eq!(&buf[..2], &[DLE, STX]);
+    assert_eq!(&buf[buf.len() - 2..], &[DLE, ETX]);
+    assert_eq!(buf.iter().filter(|b| **b == DLE).count(), 4);
+
+    // a partial frame stays in the buffer
+    buf.extend_from_slice(&[DLE, ACK, DLE, STX, 0x01]);
+    let frames = decode_frames(&mut buf);
+    assert_eq!(frames, vec![Frame::Data(data)]);
+    assert_eq!(buf, vec![DLE, STX, 0x01]);
+}
+
+#[test]
+fn test_frame_bad_checksum() {
+    let mut buf = encode_frame(&[CROSSPOINT_INTERROGATE, 0x00, 0x00, 0x05]);
+    let len = buf.len();
+    buf[len - 3] ^= 0x01;
+    assert_eq!(decode_frames(&mut buf), vec![Frame::Invalid]);
+    assert!(buf.is_empty());
+}
+
+// Destinations and sources above 127 are carried in the multiplier byte
+fn crosspoint_message(command: u8, destination: usize, source: usize) -> Vec<u8> {
+    let multiplier = (((destination / 128) & 0x07) << 4) as u8 | ((source / 128) & 0x07) as u8;
+    vec![command, 0x00, multiplier, (destination % 128) as u8, (source % 128) as u8]
+}
+
+fn crosspoint_destination(message: &[u8]) -> anyhow::Result<usize> {
+    match message {
+        [_, multiplier, destination, ..] => Ok(((*multiplier as usize >> 4) & 0x07) * 128 + *destination as usize),
+        _ => Err(anyhow!("Crosspoint message too short")),
+    }
+}
+
+fn crosspoint_source(message: &[u8]) -> anyhow::Result<usize> {
+    match message {
+        [_, multiplier, _, source, ..] => Ok((*multiplier as usize & 0x07) * 128 + *source as usize),
+        _ => Err(anyhow!("Crosspoint message too short")),
+    }
+}
+
+#[test]
+fn test_crosspoint_multiplier() {
+    let data = crosspoint_message(CROSSPOINT_CONNECT, 300, 130);
+    assert_eq!(data, vec![CROSSPOINT_CONNECT, 0x00, 0x21, 44, 2]);
+    assert_eq!(crosspoint_destination(&data[1..]).expect("Failed to get destination"), 300);
+    assert_eq!(crosspoint_source(&data[1..]).expect("Failed to get source"), 130);
+}
+
+// Names are sent in chunks, the maximum depends on the name length
+fn names_messages(command: u8, char_length: u8, start: usize, names: &[String]) -> Vec<Vec<u8>> {
+    let (width, per_message) = match char_length {
+        0 => (4, 32),
+        1 => (8, 16),
+        _ => (12, 10),
+    };
+
+    names
+        .chunks(per_message)
+        .enumerate()
+        .map(|(chunk, names)| {
+            let first = start + chunk * per_message;
+            let mut data = vec![command, 0x00, char_length, (first / 256) as u8, (first % 256) as u8, names.len() as u8];
+            for name in names {
+                data.extend(
+                    name.chars()
+                        .map(|c| if c.is_ascii() && !c.is_ascii_control() { c as u8 } else { b'?' })
+                        .chain(std::iter::repeat(b' '))
+                        .take(width),
+                );
+            }
+            data
+        })
+        .collect()
+}
+
+#[test]
+fn test_names_messages() {
+    let names: Vec<String> = (1..=10).map(|i| format!("Input {}", i)).collect();
+    let messages = names_messages(SOURCE_NAMES_RESPONSE, 1, 0, &names);
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].len(), 6 + 10 * 8);
+    assert_eq!(&messages[0][6..14], b"Input 1 ");
+
+    let messages = names_messages(SOURCE_NAMES_RESPONSE, 2, 0, &names);
+    assert_eq!(messages.len(), 1);
+    let messages = names_messages(SOURCE_NAMES_RESPONSE, 0, 0, &[names.clone(), names.clone(), names.clone(), names].concat());
+    assert_eq!(messages.len(), 2);
+    assert_eq!(&messages[1][3..6], &[0, 32, 8]);
+}
+
+/// A controller that has this much output pending is dropped.
+const MAX_PENDING_OUTPUT: usize = 1 << 20;
+
+struct Swp08Client {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    // bytes not written yet because the socket would block
+    output: Vec<u8>,
+}
+
+impl Swp08Client {
+    // Writes as much of the pending output as the socket takes
+    fn flush(&mut self) -> anyhow::Result<()> {
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => return Err(anyhow!("Connection closed")),
+                Ok(size) => {
+                    self.output.drain(..size);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if self.output.len() > MAX_PENDING_OUTPUT {
+            return Err(anyhow!("Controller does not read its messages"));
+        }
+        Ok(())
+    }
+}
+
+/// Accepts SW-P-08 controllers over TCP and translates their crosspoint and
+/// name requests to a Videohub. Only matrix 0, level 0 is served.
+pub struct Swp08Gateway {
+    hub: VideoHub,
+    listener: TcpListener,
+    clients: Vec<Swp08Client>,
+    // routes already sent as CROSSPOINT CONNECTED in this step
+    announced: Vec<(usize, usize)>,
+}
+
+impl Swp08Gateway {
+    pub fn new(hub: VideoHub, listener: TcpListener) -> anyhow::Result<Swp08Gateway> {
+        listener.set_nonblocking(true)?;
+        Ok(Swp08Gateway {
+            hub,
+            listener,
+            clients: vec![],
+            announced: vec![],
+        })
+    }
+    pub fn hub(&self) -> &VideoHub {
+        &self.hub
+    }
+    /// Runs one iteration, waiting up to `timeout` for the router to report changes.
+    pub fn step(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    debug_println!("SW-P-08 controller connected: {}", addr);
+                    stream.set_nonblocking(true)?;
+                    self.clients.push(Swp08Client {
+                        stream,
+                        buffer: vec![],
+                        output: vec![],
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let mut index = 0;
+        while index < self.clients.len() {
+            match self.read_client(index) {
+                Ok(()) => index += 1,
+                Err(e) => {
+                    debug_println!("SW-P-08 controller disconnected: {}", e);
+                    self.clients.remove(index);
+                }
+            }
+        }
+        self.flush_clients();
+
+        for change in self.hub.poll(timeout)? {
+            if let HubChange::Route { destination, source } = change {
+                // a connect made by a controller was announced when the router accepted it
+                if let Some(position) = self.announced.iter().position(|route| *route == (destination, source)) {
+                    self.announced.remove(position);
+                    continue;
+                }
+                self.broadcast(&crosspoint_message(CROSSPOINT_CONNECTED, destination, source));
+            }
+        }
+        self.announced.clear();
+        self.flush_clients();
+
+        Ok(())
+    }
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        loop {
+            self.step(Duration::from_millis(50))?;
+        }
+    }
+    fn broadcast(&mut self, message: &[u8]) {
+        let frame = encode_frame(message);
+        for client in &mut self.clients {
+            client.output.extend_from_slice(&frame);
+        }
+    }
+    fn flush_clients(&mut self) {
+        self.clients.retain_mut(|client| match client.flush() {
+            Ok(()) => true,
+            Err(e) => {
+                debug_println!("SW-P-08 controller disconnected: {}", e);
+                false
+            }
+        });
+    }
+    fn read_client(&mut self, index: usize) -> anyhow::Result<()> {
+        let client = &mut self.clients[index];
+        let mut buf = [0u8; 512];
+        loop {
+            match client.stream.read(&mut buf) {
+                Ok(0) => return Err(anyhow!("Connection closed")),
+                Ok(size) => client.buffer.extend_from_slice(&buf[..size]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        for frame in decode_frames(&mut client.buffer) {
+            let data = match frame {
+                Frame::Data(data) => data,
+                Frame::Invalid => {
+                    self.clients[index].output.extend_from_slice(&[DLE, NAK]);
+                    continue;
+                }
+            };
+            self.clients[index].output.extend_from_slice(&[DLE, ACK]);
+
+            match self.handle_message(&data) {
+                Ok(responses) => {
+                    for response in responses {
+                        self.clients[index].output.extend(encode_frame(&response));
+                    }
+                }
+                Err(e) => eprintln!("Failed to handle SW-P-08 message {:02X?}: {}", data, e),
+            }
+        }
+        Ok(())
+    }
+    // Returns the messages to send back to the controller that made the request
+    fn handle_message(&mut self, data: &[u8]) -> anyhow::Result<Vec<Vec<u8>>> {
+        let (command, message) = data.split_first().ok_or_else(|| anyhow!("Empty message"))?;
+        if message.first().is_some_and(|matrix_level| *matrix_level != 0) {
+            return Err(anyhow!("Only matrix 0, level 0 is available"));
+        }
+
+        match *command {
+            CROSSPOINT_INTERROGATE => {
+                let destination = crosspoint_destination(message)?;
+                let source = *self
+                    .hub
+                    .video_routes()
+                    .get(destination)
+                    .ok_or_else(|| anyhow!("Destination out of range: {}", destination))?;
+                Ok(vec![crosspoint_message(CROSSPOINT_TALLY, destination, source)])
+            }
+            CROSSPOINT_CONNECT => {
+                let (destination, source) = (crosspoint_destination(message)?, crosspoint_source(message)?);
+                if destination >= self.hub.output_count() || source >= self.hub.input_count() {
+                    return Err(anyhow!("Crosspoint out of range: {} -> {}", source, destination));
+                }
+                self.hub.set_routes(vec![VideoHubRoute {
+                    destination_id: destination,
+                    source_id: source,
+                }])?;
+                // every controller gets CROSSPOINT CONNECTED, also when the source was already routed
+                self.broadcast(&crosspoint_message(CROSSPOINT_CONNECTED, destination, source));
+                self.announced.push((destination, source));
+                Ok(vec![])
+            }
+            SOURCE_NAMES_REQUEST | DESTINATION_NAMES_REQUEST => {
+                let char_length = *message.get(1).ok_or_else(|| anyhow!("Names request too short"))?;
+                Ok(match *command {
+                    SOURCE_NAMES_REQUEST => names_messages(SOURCE_NAMES_RESPONSE, char_length, 0, self.hub.input_labels()),
+                    _ => names_messages(DESTINATION_NAMES_RESPONSE, char_length, 0, self.hub.output_labels()),
+                })
+            }
+            SINGLE_SOURCE_NAME_REQUEST | SINGLE_DESTINATION_NAME_REQUEST => {
+                let (char_length, index) = match message {
+                    [_, char_length, multiplier, index, ..] => (*char_length, *multiplier as usize * 256 + *index as usize),
+                    _ => return Err(anyhow!("Name request too short")),
+                };
+                let (response, labels) = match *command {
+                    SINGLE_SOURCE_NAME_REQUEST => (SOURCE_NAMES_RESPONSE, self.hub.input_labels()),
+                    _ => (DESTINATION_NAMES_RESPONSE, self.hub.output_labels()),
+                };
+                let name = labels
+                    .get(index)
+                    .ok_or_else(|| anyhow!("Name index out of range: {}", index))?;
+                Ok(names_messages(response, char_length, index, std::slice::from_ref(name)))
+            }
+            _ => Err(anyhow!("Unsupported command")),
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use anyhow::anyhow;
+use std::io::Write;
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::video_hub::{HubChange, VideoHub};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TslVersion {
+    V31,
+    V50,
+}
+
+impl FromStr for TslVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "3.1" | "v3.1" => Ok(TslVersion::V31),
+            "5" | "5.0" | "v5" | "v5.0" => Ok(TslVersion::V50),
+            _ => Err(anyhow!("Unknown TSL version: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TslTransport {
+    Udp(UdpSocket, SocketAddr),
+    Tcp(TcpStream),
+}
+
+/// Maps a destination of the router to the address of a UMD display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TslDisplay {
+    pub destination: usize,
+    pub address: u16,
+}
+
+impl FromStr for TslDisplay {
+    type Err = anyhow::Error;
+
+    // Format: <destination>=<display address>
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (destination, address) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected <destination>=<address>, got {}", s))?;
+        Ok(TslDisplay {
+            destination: destination.trim().parse()?,
+            address: address.trim().parse()?,
+        })
+    }
+}
+
+#[test]
+fn test_tsl_display_parse() {
+    let display: TslDisplay = "3=12".parse().expect("Failed to parse display mapping");
+    assert_eq!(display, TslDisplay { destination: 3, address: 12 });
+    assert!("3".parse::<TslDisplay>().is_err());
+}
+
+/// Highest display address of TSL v3.1, 127 is the broadcast address.
+pub const V31_MAX_ADDRESS: u16 = 126;
+
+// v3.1 packets are 18 bytes, the address with the high bit set, a control
+// byte and 16 characters of space padded ASCII text
+fn encode_v31(address: u16, text: &str) -> anyhow::Result<Vec<u8>> {
+    if address > V31_MAX_ADDRESS {
+        return Err(anyhow!("TSL v3.1 display address out of range: {}", address));
+    }
+    let mut packet = vec![0x80 + address as u8, 0x30];
+    packet.extend(
+        text.chars()
+            .map(|c| if c.is_ascii() && !c.is_ascii_control() { c as u8 } else { b'?' })
+            .chain(std::iter::repeat(b' '))
+            .take(16),
+    );
+    Ok(packet)
+}
+
+#[test]
+fn test_encode_v31() {
+    let packet = encode_v31(5, "Cam 1").expect("Failed to encode packet");
+    assert_eq!(packet.len(), 18);
+    assert_eq!(packet[0], 0x85);
+    assert_eq!(&packet[2..], b"Cam 1           ");
+
+    let packet = encode_v31(0, "A very long source label").expect("Failed to encode packet");
+    assert_eq!(&packet[2..], b"A very long sour");
+    assert!(encode_v31(127, "").is_err());
+}
+
+// v5.0 packets contain a header followed by a single display message,
+// all integers are little endian
+fn encode_v50(screen: u16, address: u16, text: &str) -> Vec<u8> {
+    let text: Vec<u8> = text
+        .chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() { c as u8 } else { b'?' })
+        .collect();
+
+    let mut body = vec![0x00, 0x00];
+    body.extend_from_slice(&screen.to_le_bytes());
+    body.extend_from_slice(&address.to_le_bytes());
+    // control: tally off (bits 0-5), full brightness (bits 6-7)
+    body.extend_from_slice(&0x00C0u16.to_le_bytes());
+    body.extend_from_slice(&(text.len() as u16).to_le_bytes());
+    body.extend(text);
+
+    let mut packet = (body.len() as u16).to_le_bytes().to_vec();
+    packet.extend(body);
+    packet
+}
+
+#[test]
+fn test_encode_v50() {
+    let packet = encode_v50(0, 2, "Cam 1");
+    assert_eq!(
+        packet,
+        vec![0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0xC0, 0x00, 0x05, 0x00, b'C', b'a', b'm', b' ', b'1']
+    );
+}
+
+// On TCP v5.0 packets are framed with DLE/STX and every DLE in the packet is doubled
+fn wrap_dle(packet: &[u8]) -> Vec<u8> {
+    let mut wrapped = vec![0xFE, 0x02];
+    for byte in packet {
+        if *byte == 0xFE {
+            wrapped.push(0xFE);
+        }
+        wrapped.push(*byte);
+    }
+    wrapped
+}
+
+#[test]
+fn test_wrap_dle() {
+    assert_eq!(wrap_dle(&[0x01, 0xFE, 0x02]), vec![0xFE, 0x02, 0x01, 0xFE, 0xFE, 0x02]);
+}
+
+/// Sends the label of the source routed to each mapped destination to TSL UMD displays.
+pub struct TslSender {
+    hub: VideoHub,
+    transport: TslTransport,
+    version: TslVersion,
+    displays: Vec<TslDisplay>,
+    last_refresh: Instant,
+    pub screen: u16,
+    /// Resend every display after this interval so displays that restarted catch up
+    pub refresh_interval: Duration,
+}
+
+impl TslSender {
+    /// Maps every destination to the display with the same address if `displays`
+    /// is empty, for v3.1 only the destinations up to [`V31_MAX_ADDRESS`].
+    /// Fails if a display shows a destination the router does not have or has
+    /// an address the TSL version cannot reach.
+    pub fn new(hub: VideoHub, transport: TslTransport, version: TslVersion, displays: Vec<TslDisplay>) -> anyhow::Result<TslSender> {
+        let max_address = match version {
+            TslVersion::V31 => V31_MAX_ADDRESS,
+            TslVersion::V50 => u16::MAX,
+        };
+        let displays = if displays.is_empty() {
+            (0..hub.output_count().min(max_address as usize + 1))
+                .map(|destination| TslDisplay {
+                    destination,
+                    address: destination as u16,
+                })
+                .collect()
+        } else {
+            displays
+        };
+        for display in &displays {
+            if display.destination >= hub.output_count() {
+                return Err(anyhow!(
+                    "Display {} shows destination {} (0-based), the router has {} outputs",
+                    display.address,
+                    display.destination,
+                    hub.output_count()
+                ));
+            }
+            if display.address > max_address {
+                return Err(anyhow!("TSL v3.1 display address out of range: {}", display.address));
+            }
+        }
+        Ok(TslSender {
+            hub,
+            transport,
+            version,
+            displays,
+            last_refresh: Instant::now(),
+            screen: 0,
+            refresh_interval: Duration::from_secs(10),
+        })
+    }
+    pub fn hub(&self) -> &VideoHub {
+        &self.hub
+    }
+    /// Label of the source currently routed to `destination`
+    pub fn display_text(&self, destination: usize) -> String {
+        self.hub
+            .video_routes()
+            .get(destination)
+            .and_then(|source| self.hub.input_labels().get(*source))
+            .cloned()
+            .unwrap_or_default()
+    }
+    fn send_display(&mut self, display: TslDisplay) -> anyhow::Result<()> {
+        let text = self.display_text(display.destination);
+        let packet = match self.version {
+            TslVersion::V31 => encode_v31(display.address, &text)?,
+            TslVersion::V50 => encode_v50(self.screen, display.address, &text),
+        };
+        match &mut self.transport {
+            TslTransport::Udp(socket, addr) => {
+                socket.send_to(&packet, *addr)?;
+            }
+            TslTransport::Tcp(stream) if self.version == TslVersion::V50 => stream.write_all(&wrap_dle(&packet))?,
+            TslTransport::Tcp(stream) => stream.write_all(&packet)?,
+        }
+        Ok(())
+    }
+    pub fn send_all(&mut self) -> anyhow::Result<()> {
+        self.last_refresh = Instant::now();
+        for display in self.displays.clone() {
+            self.send_display(display)?;
+        }
+        Ok(())
+    }
+    /// Runs one iteration, waiting up to `timeout` for the router to report changes.
+    pub fn step(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        let changes = self.hub.poll(timeout)?;
+
+        if self.last_refresh.elapsed() > self.refresh_interval {
+            return self.send_all();
+        }
+
+        let routes = self.hub.video_routes().clone();
+        let displays: Vec<TslDisplay> = self
+            .displays
+            .iter()
+            .filter(|display| {
+                changes.iter().any(|change| match change {
+                    HubChange::Route { destination, .. } => *destination == display.destination,
+                    HubChange::InputLabel { index, .. } => routes.get(display.destination) == Some(index),
+                    _ => false,
+                })
+            })
+            .copied()
+            .collect();
+
+        for display in displays {
+            self.send_display(display)?;
+        }
+        Ok(())
+    }
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        self.send_all()?;
+        loop {
+            self.step(Duration::from_millis(100))?;
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::video_hub::VideoHubDump;
+
+/// A problem found in a dump, `path` points at the offending value, e.g. `$.routes[3].sourceId`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+fn error(path: &str, message: String) -> ValidationError {
+    ValidationError {
+        path: path.to_string(),
+        message,
+    }
+}
+
+/// Checks a dump against the dump schema and for semantic errors, returning every problem found.
+///
+/// Older format versions are migrated before they are checked. Ids are only
+/// checked against the size of the router if `inputs` and `outputs` are given.
+pub fn validate_dump(json: &str, inputs: Option<usize>, outputs: Option<usize>) -> Vec<ValidationError> {
+    let mut value: Value = match serde_json::from_str(json) {
+        Ok(value) => value,
+        Err(e) => return vec![error("$", format!("Invalid JSON: {}", e))],
+    };
+    if let Err(e) = VideoHubDump::migrate(&mut value) {
+        return vec![error("$", e.to_string())];
+    }
+
+    let schema = VideoHubDump::json_schema();
+    let mut errors = Vec::new();
+    validate_schema(&value, &schema, &schema, "$", &mut errors);
+    validate_semantics(&value, inputs, outputs, &mut errors);
+    errors
+}
+
+// Supports the subset of JSON Schema generated for the dump types
+fn validate_schema(value: &Value, schema: &Value, root: &Value, path: &str, errors: &mut Vec<ValidationError>) {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let resolved = reference
+            .strip_prefix("#/")
+            .map(|pointer| root.pointer(&format!("/{}", pointer)));
+        match resolved {
+            Some(Some(resolved)) => validate_schema(value, resolved, root, path, errors),
+            _ => errors.push(error(path, format!("Unresolved schema reference {}", reference))),
+        }
+        return;
+    }
+
+    if let Some(expected) = schema.get("type").and_then(Value::as_str) {
+        let matches = match expected {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_u64() || value.is_i64(),
+            "number" => value.is_number(),
+            "boolean" => value.is_boolean(),
+            _ => true,
+        };
+        if !matches {
+            errors.push(error(path, format!("Expected {}, found {}", expected, type_name(value))));
+            return;
+        }
+    }
+
+    if let (Some(minimum), Some(number)) = (schema.get("minimum").and_then(Value::as_f64), value.as_f64()) {
+        if number < minimum {
+            errors.push(error(path, format!("Must be at least {}", minimum)));
+        }
+    }
+    if let (Some(maximum), Some(number)) = (schema.get("maximum").and_then(Value::as_f64), value.as_f64()) {
+        if number > maximum {
+            errors.push(error(path, format!("Must be at most {}", maximum)));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        validate_object(object, schema, root, path, errors);
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (i, item) in array.iter().enumerate() {
+            validate_schema(item, items, root, &format!("{}[{}]", path, i), errors);
+        }
+    }
+}
+
+fn validate_object(
+    object: &Map<String, Value>,
+    schema: &Value,
+    root: &Value,
+    path: &str,
+    errors: &mut Vec<ValidationError>,
+) {
+    if let Some(required) = schema.get("required").and_then(Value::as_array) {
+        for key in required.iter().filter_map(Value::as_str) {
+            if !object.contains_key(key) {
+                errors.push(error(path, format!("Missing required property {}", key)));
+            }
+        }
+    }
+    if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+        for (key, property) in properties {
+            if let Some(value) = object.get(key) {
+                validate_schema(value, property, root, &format!("{}.{}", path, key), errors);
+            }
+        }
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// Runs on the raw value so semantic errors are reported next to schema errors
+fn validate_semantics(dump: &Value, inputs: Option<usize>, outputs: Option<usize>, errors: &mut Vec<ValidationError>) {
+    let id_base = dump.get("idBase").and_then(Value::as_u64).unwrap_or(0);
+    let inputs = inputs.map(|count| (id_base, count));
+    let outputs = outputs.map(|count| (id_base, count));
+
+    let sources = validate_labels(dump, "sources", "Source", inputs, errors);
+    let destinations = validate_labels(dump, "destinations", "Destination", outputs, errors);
+
+    let routes = match dump.get("routes").and_then(Value::as_array) {
+        Some(routes) => routes,
+        None => return,
+    };
+    let mut routed: HashMap<u64, usize> = HashMap::new();
+    for (i, route) in routes.iter().enumerate() {
+        let path = format!("$.routes[{}]", i);
+
+        if let Some(destination) = route.get("destinationId").and_then(Value::as_u64) {
+            let id_path = format!("{}.destinationId", path);
+            if let Some(first) = routed.insert(destination, i) {
+                errors.push(error(&id_path, format!("Destination {} is already routed at $.routes[{}]", destination, first)));
+            }
+            check_range(destination, outputs, "Destination", &id_path, errors);
+            if !destinations.is_empty() && !destinations.contains_key(&destination) {
+                errors.push(error(&id_path, format!("Route points at missing destination {}", destination)));
+            }
+        }
+
+        if let Some(source) = route.get("sourceId").and_then(Value::as_u64) {
+            let id_path = format!("{}.sourceId", path);
+            check_range(source, inputs, "Source", &id_path, errors);
+            if !sources.is_empty() && !sources.contains_key(&source) {
+                errors.push(error(&id_path, format!("Route points at missing source {}", source)));
+            }
+        }
+    }
+}
+
+// Checks a label section and returns the index of the first label with each id
+fn validate_labels(
+    dump: &Value,
+    section: &str,
+    kind: &str,
+    range: Option<(u64, usize)>,
+    errors: &mut Vec<ValidationError>,
+) -> HashMap<u64, usize> {
+    let mut ids = HashMap::new();
+    let labels = match dump.get(section).and_then(Value::as_array) {
+        Some(labels) => labels,
+        None => return ids,
+    };
+
+    for (i, label) in labels.iter().enumerate() {
+        let id = match label.get("id").and_then(Value::as_u64) {
+            Some(id) => id,
+            None => continue,
+        };
+        let path = format!("$.{}[{}].id", section, i);
+        match ids.get(&id) {
+            Some(first) => errors.push(error(&path, format!("Duplicate {} id {}, first used at $.{}[{}]", kind.to_lowercase(), id, section, first))),
+            None => {
+                ids.insert(id, i);
+            }
+        }
+        check_range(id, range, kind, &path, errors);
+    }
+    ids
+}
+
+// `range` is the id base of the dump and the number of inputs or outputs of the router
+fn check_range(id: u64, range: Option<(u64, usize)>, kind: &str, path: &str, errors: &mut Vec<ValidationError>) {
+    if let Some((id_base, count)) = range {
+        if id < id_base || id >= id_base + count as u64 {
+            errors.push(error(
+                path,
+                format!("{} id {} is out of range, the router has {} to {}", kind, id, id_base, (id_base + count as u64).saturating_sub(1)),
+            ));
+        }
+    }
+}
+
+#[test]
+fn test_validate_schema_errors() {
+    let errors = validate_dump(
+        r#"{"formatVersion": 2, "time": 0, "name": 5, "sources": [{"id": -1, "name": "a"}], "destinations": [], "routes": [{"destinationId": 0}]}"#,
+        None,
+        None,
+    );
+    let paths: Vec<&str> = errors.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(paths, vec!["$.name", "$.routes[0]", "$.sources[0].id"]);
+}
+
+#[test]
+fn test_validate_semantic_errors() {
+    let errors = validate_dump(
+        r#"{
+    "formatVersion": 2,
+    "time": 0,
+    "name": "test",
+    "sources": [{"id": 0, "name": "a"}, {"id": 0, "name": "b"}],
+    "destinations": [{"id": 0, "name": "a"}, {"id": 1, "name": "b"}],
+    "routes": [
+        {"destinationId": 0, "sourceId": 3},
+        {"destinationId": 0, "sourceId": 0},
+        {"destinationId": 1, "sourceId": 0}
+    ]
+}"#,
+        Some(2),
+        Some(1),
+    );
+    assert_eq!(
+        errors,
+        vec![
+            error("$.sources[1].id", "Duplicate source id 0, first used at $.sources[0]".to_string()),
+            error("$.destinations[1].id", "Destination id 1 is out of range, the router has 0 to 0".to_string()),
+            error("$.routes[0].sourceId", "Source id 3 is out of range, the router has 0 to 1".to_string()),
+            error("$.routes[0].sourceId", "Route points at missing source 3".to_string()),
+            error("$.routes[1].destinationId", "Destination 0 is already routed at $.routes[0]".to_string()),
+            error("$.routes[2].destinationId", "Destination id 1 is out of range, the router has 0 to 0".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_validate_one_based_ranges() {
+    let errors = validate_dump(
+        r#"{"formatVersion": 3, "time": 0, "idBase": 1, "name": "test", "sources": [{"id": 0, "name": "a"}, {"id": 2, "name": "b"}], "destinations": [], "routes": []}"#,
+        Some(2),
+        Some(2),
+    );
+    assert_eq!(
+        errors,
+        vec![error("$.sources[0].id", "Source id 0 is out of range, the router has 1 to 2".to_string())]
+    );
+}
+
+#[test]
+fn test_validate_legacy_dump() {
+    let errors = validate_dump(
+        r#"{"timestamp": 0, "name": "test", "sources": [], "destinations": [], "routes": [{"destinationId": 0, "routeId": 0}]}"#,
+        None,
+        None,
+    );
+    assert!(errors.is_empty(), "{:?}", errors);
+}
@@ -0,0 +1,64 @@
+use crate::audit::{AuditChange, AuditEntry, AuditLog, AuditOrigin};
+
+impl VideoHub {
+    /// Appends every route, label and lock change made by this client or
+    /// reported by the router to `log`, or stops logging with `None`.
+    pub fn set_audit_log(&mut self, log: Option<AuditLog>) {
+        self.audit_log = log;
+    }
+    // Queues the changes a block is about to make for the audit log
+    fn audit(&mut self, block: &HubMessage, external: bool) {
+        if self.audit_log.is_none() {
+            return;
+        }
+        let diff = self.block_diff(block);
+        let mut changes: Vec<AuditChange> = Vec::new();
+        changes.extend(diff.sources.into_iter().map(|label| AuditChange::InputLabel {
+            id: label.id,
+            from: label.from,
+            to: label.to,
+        }));
+        changes.extend(diff.destinations.into_iter().map(|label| AuditChange::OutputLabel {
+            id: label.id,
+            from: label.from,
+            to: label.to,
+        }));
+        changes.extend(diff.routes.into_iter().map(|route| AuditChange::Route {
+            destination: route.destination_id,
+            from: route.from,
+            to: route.to,
+        }));
+        if let HubMessage::OutputLocks(locks) = block {
+            for lock in &locks.locks {
+                match self.output_locks.get(lock.destination) {
+                    Some(from) if *from != lock.state => changes.push(AuditChange::Lock {
+                        destination: lock.destination,
+                        from: *from,
+                        to: lock.state,
+                    }),
+                    _ => continue,
+                }
+            }
+        }
+
+        let time = crate::watch::format_time(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default());
+        let origin = if external { AuditOrigin::External } else { AuditOrigin::Local };
+        if let Some(log) = &mut self.audit_log {
+            for change in changes {
+                log.push(AuditEntry {
+                    time: time.clone(),
+                    hub: self.unique_id.clone(),
+                    origin,
+                    change,
+                });
+            }
+        }
+    }
+    // Writes the changes queued for the audit log. A failed write is reported
+    // on its own, it does not make the change that was logged fail.
+    fn flush_audit_log(&mut self) {
+        if let Some(Err(e)) = self.audit_log.as_mut().map(AuditLog::flush) {
+            eprintln!("{:#}", e);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+
+/// Changes an import would make to a Videohub, see [`VideoHub::diff`].
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpDiff {
+    /// Number of the first input and output, as in [`VideoHubDump::id_base`]
+    pub id_base: usize,
+    pub sources: Vec<LabelDiff>,
+    pub destinations: Vec<LabelDiff>,
+    pub routes: Vec<RouteDiff>,
+    /// Destinations with a route change that are locked by another client
+    pub locked: Vec<usize>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LabelDiff {
+    pub id: usize,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteDiff {
+    pub destination_id: usize,
+    pub from: usize,
+    pub to: usize,
+}
+
+impl DumpDiff {
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty() && self.destinations.is_empty() && self.routes.is_empty()
+    }
+    /// Renumbers all ids from 0-based to `id_base`.
+    pub fn with_id_base(mut self, id_base: usize) -> DumpDiff {
+        let shift = |id: &mut usize| *id = *id - self.id_base + id_base;
+        self.sources.iter_mut().for_each(|label| shift(&mut label.id));
+        self.destinations.iter_mut().for_each(|label| shift(&mut label.id));
+        for route in &mut self.routes {
+            shift(&mut route.destination_id);
+            shift(&mut route.from);
+            shift(&mut route.to);
+        }
+        self.locked.iter_mut().for_each(shift);
+        self.id_base = id_base;
+        self
+    }
+    /// Swaps the old and new values, giving the diff that undoes this one.
+    pub fn reversed(mut self) -> DumpDiff {
+        let swap_label = |label: &mut LabelDiff| std::mem::swap(&mut label.from, &mut label.to);
+        self.sources.iter_mut().for_each(swap_label);
+        self.destinations.iter_mut().for_each(swap_label);
+        self.routes.iter_mut().for_each(|route| std::mem::swap(&mut route.from, &mut route.to));
+        self
+    }
+    fn append(&mut self, other: DumpDiff) {
+        self.sources.extend(other.sources);
+        self.destinations.extend(other.destinations);
+        self.routes.extend(other.routes);
+        self.locked.extend(other.locked);
+    }
+    // Splits the diff into parts of a single section with at most IMPORT_BATCH_SIZE entries
+    fn batches(&self) -> Vec<DumpDiff> {
+        let batch = || DumpDiff {
+            id_base: self.id_base,
+            ..Default::default()
+        };
+        let mut batches = Vec::new();
+        for chunk in self.sources.chunks(IMPORT_BATCH_SIZE) {
+            batches.push(DumpDiff { sources: chunk.to_vec(), ..batch() });
+        }
+        for chunk in self.destinations.chunks(IMPORT_BATCH_SIZE) {
+            batches.push(DumpDiff { destinations: chunk.to_vec(), ..batch() });
+        }
+        for chunk in self.routes.chunks(IMPORT_BATCH_SIZE) {
+            let locked = self
+                .locked
+                .iter()
+                .copied()
+                .filter(|destination| chunk.iter().any(|route| route.destination_id == *destination))
+                .collect();
+            batches.push(DumpDiff { routes: chunk.to_vec(), locked, ..batch() });
+        }
+        batches
+    }
+    // The block applying a batch, which must only contain a single section
+    fn message(&self) -> HubMessage {
+        let labels = |labels: &[LabelDiff]| LabelList {
+            labels: labels.iter().map(|label| Label { name: label.to.clone(), index: label.id }).collect(),
+        };
+        if !self.sources.is_empty() {
+            HubMessage::InputLabels(labels(&self.sources))
+        } else if !self.destinations.is_empty() {
+            HubMessage::OutputLabels(labels(&self.destinations))
+        } else {
+            HubMessage::VideoRouting(VideoRouting {
+                routes: self.routes.iter().map(|route| Route { destination: route.destination_id, source: route.to }).collect(),
+            })
+        }
+    }
+}
+
+impl fmt::Display for DumpDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+        for label in &self.sources {
+            writeln!(f, "Input label {}: {:?} -> {:?}", label.id, label.from, label.to)?;
+        }
+        for label in &self.destinations {
+            writeln!(f, "Output label {}: {:?} -> {:?}", label.id, label.from, label.to)?;
+        }
+        for route in &self.routes {
+            let locked = if self.locked.contains(&route.destination_id) { " (locked)" } else { "" };
+            writeln!(f, "Route {}: {} -> {}{}", route.destination_id, route.from, route.to, locked)?;
+        }
+        Ok(())
+    }
+}
+
+impl VideoHub {
+    /// Compares the current state with the parts of a dump selected by `filter`
+    /// without sending anything. The diff uses 0-based ids.
+    pub fn diff(&self, dump: VideoHubDump, filter: &ImportFilter) -> anyhow::Result<DumpDiff> {
+        let dump = self.prepare_import(dump, filter)?;
+
+        let label_diff = |current: &[String], labels: Vec<VideoHubLabel>| -> Vec<LabelDiff> {
+            labels
+                .into_iter()
+                .filter_map(|label| match current.get(label.id) {
+                    Some(from) if *from != label.name => Some(LabelDiff { id: label.id, from: from.clone(), to: label.name }),
+                    _ => None,
+                })
+                .collect()
+        };
+        let sources = label_diff(self.input_labels(), dump.sources);
+        let destinations = label_diff(self.output_labels(), dump.destinations);
+
+        let routes: Vec<RouteDiff> = dump
+            .routes
+            .iter()
+            .filter_map(|route| match self.video_routes().get(route.destination_id) {
+                Some(from) if *from != route.source_id => Some(RouteDiff {
+                    destination_id: route.destination_id,
+                    from: *from,
+                    to: route.source_id,
+                }),
+                _ => None,
+            })
+            .collect();
+        let locked = routes
+            .iter()
+            .map(|route| route.destination_id)
+            .filter(|destination| self.output_locks().get(*destination) == Some(&LockState::Locked))
+            .collect();
+
+        Ok(DumpDiff {
+            id_base: 0,
+            sources,
+            destinations,
+            routes,
+            locked,
+        })
+    }
+}
+
+#[test]
+fn test_diff_display() {
+    let diff = DumpDiff {
+        id_base: 0,
+        sources: vec![LabelDiff { id: 0, from: "Input 1".to_string(), to: "Camera 1".to_string() }],
+        destinations: vec![],
+        routes: vec![
+            RouteDiff { destination_id: 1, from: 1, to: 4 },
+            RouteDiff { destination_id: 2, from: 2, to: 0 },
+        ],
+        locked: vec![2],
+    }
+    .with_id_base(1);
+    assert_eq!(
+        diff.to_string(),
+        "Input label 1: \"Input 1\" -> \"Camera 1\"\nRoute 2: 2 -> 5\nRoute 3: 3 -> 1 (locked)\n"
+    );
+    assert_eq!(DumpDiff::default().to_string(), "No changes\n");
+}
+
+#[test]
+fn test_diff_batches() {
+    let diff = DumpDiff {
+        id_base: 0,
+        sources: vec![LabelDiff { id: 3, from: "Input 4".to_string(), to: "Camera 4".to_string() }],
+        destinations: vec![],
+        routes: (0..IMPORT_BATCH_SIZE + 1).map(|id| RouteDiff { destination_id: id, from: id, to: 0 }).collect(),
+        locked: vec![IMPORT_BATCH_SIZE],
+    };
+    let batches = diff.batches();
+    assert_eq!(batches.len(), 3);
+    match batches[0].message() {
+        HubMessage::InputLabels(labels) => assert_eq!(labels.serialize(), "3 Camera 4\n"),
+        message => panic!("Unexpected message {:?}", message),
+    }
+    assert_eq!(batches[1].routes.len(), IMPORT_BATCH_SIZE);
+    assert!(batches[1].locked.is_empty());
+    assert_eq!(batches[2].locked, vec![IMPORT_BATCH_SIZE]);
+    match batches[2].clone().reversed().message() {
+        HubMessage::VideoRouting(routes) => assert_eq!(routes.serialize(), format!("{0} {0}\n", IMPORT_BATCH_SIZE)),
+        message => panic!("Unexpected message {:?}", message),
+    }
+}
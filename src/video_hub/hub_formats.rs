@@ -0,0 +1,287 @@
+use std::path::Path;
+
+/// File formats a dump can be read from and written to.
+///
+/// CSV only holds labels and routes, one row per input or output, and has no
+/// `idBase` field, so its ids are read with the id base given to [`VideoHubDump::parse`].
+/// `Videohub` is the block format of the Videohub protocol, as used by the label
+/// and routing files of Blackmagic's Videohub software, its ids are always 0-based.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DumpFormat {
+    #[default]
+    Json,
+    Yaml,
+    Toml,
+    Csv,
+    Videohub,
+}
+
+impl DumpFormat {
+    /// Picks the format from the extension of `path`, `.txt` files are label
+    /// and routing files of the Videohub software.
+    pub fn from_path(path: &Path) -> Option<DumpFormat> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "txt" => Some(DumpFormat::Videohub),
+            extension => extension.parse().ok(),
+        }
+    }
+}
+
+impl FromStr for DumpFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(DumpFormat::Json),
+            "yaml" | "yml" => Ok(DumpFormat::Yaml),
+            "toml" => Ok(DumpFormat::Toml),
+            "csv" => Ok(DumpFormat::Csv),
+            "videohub" => Ok(DumpFormat::Videohub),
+            _ => Err(anyhow!("Unknown dump format {}, expected json, yaml, toml, csv or videohub", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct CsvRow {
+    #[serde(rename = "type")]
+    kind: String,
+    id: usize,
+    label: String,
+    source: Option<usize>,
+}
+
+impl VideoHubDump {
+    /// Parses a dump in any of the supported formats, `id_base` is only used for CSV.
+    pub fn parse(text: &str, format: DumpFormat, id_base: usize) -> anyhow::Result<VideoHubDump> {
+        match format {
+            DumpFormat::Json => VideoHubDump::from_json(text),
+            DumpFormat::Yaml => VideoHubDump::from_value(serde_yaml::from_str(text).with_context(|| "Invalid YAML")?),
+            DumpFormat::Toml => VideoHubDump::from_value(toml::from_str(text).with_context(|| "Invalid TOML")?),
+            DumpFormat::Csv => VideoHubDump::from_csv(text, id_base),
+            DumpFormat::Videohub => VideoHubDump::from_blocks(text),
+        }
+    }
+    pub fn to_format(&self, format: DumpFormat) -> anyhow::Result<String> {
+        match format {
+            DumpFormat::Json => self.to_json(),
+            DumpFormat::Yaml => serde_yaml::to_string(self).with_context(|| "Failed to create YAML dump"),
+            // TOML has no 128 bit integers, the time fits into 64 bits as JSON number
+            DumpFormat::Toml => toml::to_string_pretty(&serde_json::to_value(self)?).with_context(|| "Failed to create TOML dump"),
+            DumpFormat::Csv => self.to_csv(),
+            DumpFormat::Videohub => self.to_blocks(),
+        }
+    }
+    /// Reads labels and routes from `type,id,label,source` rows, where `type` is
+    /// `input` or `output`. Empty labels are left out and `source` is only used
+    /// for outputs, where it sets the route.
+    pub fn from_csv(csv: &str, id_base: usize) -> anyhow::Result<VideoHubDump> {
+        let mut dump = VideoHubDump {
+            format_version: DUMP_FORMAT_VERSION,
+            time: 0,
+            id_base,
+            name: String::new(),
+            sources: Vec::new(),
+            destinations: Vec::new(),
+            routes: Vec::new(),
+        };
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(csv.as_bytes());
+        for (i, row) in reader.deserialize().enumerate() {
+            // the header is line 1
+            let line = i + 2;
+            let row: CsvRow = row.with_context(|| format!("Invalid CSV row on line {}", line))?;
+            let label = || VideoHubLabel {
+                id: row.id,
+                name: row.label.clone(),
+            };
+            match row.kind.as_str() {
+                "input" if !row.label.is_empty() => dump.sources.push(label()),
+                "input" => {}
+                "output" => {
+                    if !row.label.is_empty() {
+                        dump.destinations.push(label());
+                    }
+                    if let Some(source_id) = row.source {
+                        dump.routes.push(VideoHubRoute {
+                            destination_id: row.id,
+                            source_id,
+                        });
+                    }
+                }
+                kind => return Err(anyhow!("Unknown type {} on line {}, expected input or output", kind, line)),
+            }
+        }
+        Ok(dump)
+    }
+    /// Writes labels and routes as CSV, see [`VideoHubDump::from_csv`].
+    pub fn to_csv(&self) -> anyhow::Result<String> {
+        let mut rows: Vec<CsvRow> = self
+            .sources
+            .iter()
+            .map(|label| CsvRow {
+                kind: "input".to_string(),
+                id: label.id,
+                label: label.name.clone(),
+                source: None,
+            })
+            .collect();
+        let route = |id: usize| self.routes.iter().find(|route| route.destination_id == id).map(|route| route.source_id);
+        rows.extend(self.destinations.iter().map(|label| CsvRow {
+            kind: "output".to_string(),
+            id: label.id,
+            label: label.name.clone(),
+            source: route(label.id),
+        }));
+        // routes of outputs without a label
+        rows.extend(
+            self.routes
+                .iter()
+                .filter(|route| !self.destinations.iter().any(|label| label.id == route.destination_id))
+                .map(|route| CsvRow {
+                    kind: "output".to_string(),
+                    id: route.destination_id,
+                    label: String::new(),
+                    source: Some(route.source_id),
+                }),
+        );
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for row in rows {
+            writer.serialize(row)?;
+        }
+        let csv = writer.into_inner().with_context(|| "Failed to create CSV dump")?;
+        Ok(String::from_utf8(csv)?)
+    }
+}
+
+impl VideoHubDump {
+    /// Reads `INPUT LABELS:`, `OUTPUT LABELS:` and `VIDEO OUTPUT ROUTING:` blocks,
+    /// the model name is taken from a `VIDEOHUB DEVICE:` block if there is one.
+    pub fn from_blocks(text: &str) -> anyhow::Result<VideoHubDump> {
+        let mut dump = VideoHubDump {
+            format_version: DUMP_FORMAT_VERSION,
+            time: 0,
+            id_base: 0,
+            name: String::new(),
+            sources: Vec::new(),
+            destinations: Vec::new(),
+            routes: Vec::new(),
+        };
+        let labels = |list: LabelList| -> Vec<VideoHubLabel> {
+            list.labels
+                .into_iter()
+                .map(|label| VideoHubLabel {
+                    id: label.index,
+                    name: label.name,
+                })
+                .collect()
+        };
+        // files saved on Windows use CRLF line endings
+        for block in HubMessage::parse_blocks(&text.replace("\r\n", "\n"))? {
+            match block {
+                HubMessage::DeviceInfo(device_info) => dump.name = device_info.model,
+                HubMessage::InputLabels(list) => dump.sources.extend(labels(list)),
+                HubMessage::OutputLabels(list) => dump.destinations.extend(labels(list)),
+                HubMessage::VideoRouting(routing) => dump.routes.extend(routing.routes.into_iter().map(|route| VideoHubRoute {
+                    destination_id: route.destination,
+                    source_id: route.source,
+                })),
+                _ => {}
+            }
+        }
+        if dump.sources.is_empty() && dump.destinations.is_empty() && dump.routes.is_empty() {
+            return Err(anyhow!("No labels or routes found in Videohub file"));
+        }
+        Ok(dump)
+    }
+    /// Writes the labels and routes as Videohub protocol blocks with 0-based ids.
+    pub fn to_blocks(&self) -> anyhow::Result<String> {
+        let dump = self.clone().with_id_base(0)?;
+        let labels = |labels: &[VideoHubLabel]| LabelList {
+            labels: labels
+                .iter()
+                .map(|label| Label {
+                    name: label.name.clone(),
+                    index: label.id,
+                })
+                .collect(),
+        };
+        let routing = VideoRouting {
+            routes: dump
+                .routes
+                .iter()
+                .map(|route| Route {
+                    destination: route.destination_id,
+                    source: route.source_id,
+                })
+                .collect(),
+        };
+        let blocks = [
+            HubMessage::InputLabels(labels(&dump.sources)),
+            HubMessage::OutputLabels(labels(&dump.destinations)),
+            HubMessage::VideoRouting(routing),
+        ];
+        // empty sections are left out, an empty block would be a query
+        let text = blocks
+            .iter()
+            .filter_map(|block| {
+                let lines = match block {
+                    HubMessage::InputLabels(list) | HubMessage::OutputLabels(list) => list.serialize(),
+                    HubMessage::VideoRouting(routing) => routing.serialize(),
+                    _ => String::new(),
+                };
+                (!lines.is_empty()).then(|| format!("{}\n{}\n", block.get_header(), lines))
+            })
+            .collect();
+        Ok(text)
+    }
+}
+
+#[test]
+fn test_dump_blocks() {
+    let text = "VIDEOHUB DEVICE:\r\nDevice present: true\r\nModel name: Blackmagic Smart Videohub 20 x 20\r\n\r\nINPUT LABELS:\r\n0 Camera 1\r\n1 Camera 2\r\n\r\nVIDEO OUTPUT ROUTING:\r\n3 1\r\n\r\n";
+    let dump = VideoHubDump::from_blocks(text).expect("Failed to read Videohub file");
+    assert_eq!(dump.name, "Blackmagic Smart Videohub 20 x 20");
+    assert_eq!(dump.sources[1], VideoHubLabel { id: 1, name: "Camera 2".to_string() });
+    assert_eq!(dump.routes, vec![VideoHubRoute { destination_id: 3, source_id: 1 }]);
+
+    let written = dump.with_id_base(1).and_then(|dump| dump.to_blocks()).expect("Failed to write Videohub file");
+    assert_eq!(written, "INPUT LABELS:\n0 Camera 1\n1 Camera 2\n\nVIDEO OUTPUT ROUTING:\n3 1\n\n");
+    assert!(VideoHubDump::from_blocks("PROTOCOL PREAMBLE:\nVersion: 2.3\n\n").is_err());
+}
+
+#[test]
+fn test_dump_formats_round_trip() {
+    let dump = VideoHubDump {
+        format_version: DUMP_FORMAT_VERSION,
+        time: 1742323854265,
+        id_base: 1,
+        name: "Blackmagic Smart Videohub 20 x 20".to_string(),
+        sources: vec![VideoHubLabel { id: 1, name: "Camera, wide".to_string() }],
+        destinations: vec![VideoHubLabel { id: 1, name: "Monitor 1".to_string() }],
+        routes: vec![
+            VideoHubRoute { destination_id: 1, source_id: 1 },
+            VideoHubRoute { destination_id: 2, source_id: 1 },
+        ],
+    };
+    for format in [DumpFormat::Json, DumpFormat::Yaml, DumpFormat::Toml] {
+        let text = dump.to_format(format).expect("Failed to write dump");
+        assert_eq!(VideoHubDump::parse(&text, format, 0).expect("Failed to read dump"), dump, "{:?}", format);
+    }
+
+    let csv = dump.to_csv().expect("Failed to write CSV");
+    assert_eq!(csv, "type,id,label,source\ninput,1,\"Camera, wide\",\noutput,1,Monitor 1,1\noutput,2,,1\n");
+    let parsed = VideoHubDump::from_csv(&csv, 1).expect("Failed to read CSV");
+    assert_eq!(parsed.sources, dump.sources);
+    assert_eq!(parsed.destinations, dump.destinations);
+    assert_eq!(parsed.routes, dump.routes);
+}
+
+#[test]
+fn test_dump_format_from_path() {
+    assert_eq!(DumpFormat::from_path(Path::new("show.YML")), Some(DumpFormat::Yaml));
+    assert_eq!(DumpFormat::from_path(Path::new("labels.csv")), Some(DumpFormat::Csv));
+    assert_eq!(DumpFormat::from_path(Path::new("labels.TXT")), Some(DumpFormat::Videohub));
+    assert_eq!(DumpFormat::from_path(Path::new("dump")), None);
+    assert!(VideoHubDump::from_csv("type,id,label,source\npanel,1,a,\n", 0).is_err());
+}
@@ -0,0 +1,160 @@
+/// How many changes [`VideoHub::undo`] can go back.
+pub const HISTORY_LIMIT: usize = 100;
+
+/// The route or label changes of a single block, undone and redone together.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    /// The change with 0-based ids
+    pub diff: DumpDiff,
+    /// Made by another client, only recorded after [`VideoHub::record_external_changes`]
+    pub external: bool,
+}
+
+#[derive(Debug, Default)]
+struct History {
+    undo: Vec<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+    record_external: bool,
+    // set while undoing or redoing, so the change is not recorded again
+    replaying: bool,
+}
+
+impl VideoHub {
+    /// Changes that can be undone, the most recent last.
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history.undo
+    }
+    /// Undone changes that can be redone, the next one last.
+    pub fn redo_history(&self) -> &[HistoryEntry] {
+        &self.history.redo
+    }
+    /// Also records route and label changes made by other clients, so they can be undone.
+    pub fn record_external_changes(&mut self, record: bool) {
+        self.history.record_external = record;
+    }
+    pub fn clear_history(&mut self) {
+        self.history.undo.clear();
+        self.history.redo.clear();
+    }
+    /// Reverts the last recorded change and returns it, or `None` if there is
+    /// nothing to undo. The change stays in the history if the router refuses it.
+    pub fn undo(&mut self) -> anyhow::Result<Option<HistoryEntry>> {
+        let Some(entry) = self.history.undo.pop() else {
+            return Ok(None);
+        };
+        match self.replay(entry.diff.clone().reversed()) {
+            Ok(()) => {
+                self.history.redo.push(entry.clone());
+                Ok(Some(entry))
+            }
+            Err(e) => {
+                self.history.undo.push(entry);
+                Err(e.context("Failed to undo"))
+            }
+        }
+    }
+    /// Applies the last undone change again and returns it, or `None` if there
+    /// is nothing to redo.
+    pub fn redo(&mut self) -> anyhow::Result<Option<HistoryEntry>> {
+        let Some(entry) = self.history.redo.pop() else {
+            return Ok(None);
+        };
+        match self.replay(entry.diff.clone()) {
+            Ok(()) => {
+                self.history.undo.push(entry.clone());
+                Ok(Some(entry))
+            }
+            Err(e) => {
+                self.history.redo.push(entry);
+                Err(e.context("Failed to redo"))
+            }
+        }
+    }
+    fn replay(&mut self, diff: DumpDiff) -> anyhow::Result<()> {
+        self.history.replaying = true;
+        let result = self.send_message(diff.message());
+        self.history.replaying = false;
+        result
+    }
+    // Records the changes a block is about to make, the audit log gets all of
+    // them while the history only keeps what can be undone
+    fn record(&mut self, block: &HubMessage, external: bool) {
+        self.audit(block, external);
+        if (external && !self.history.record_external) || (!external && self.history.replaying) {
+            return;
+        }
+        let diff = self.block_diff(block);
+        if diff.is_empty() {
+            return;
+        }
+        self.history.undo.push(HistoryEntry { diff, external });
+        if self.history.undo.len() > HISTORY_LIMIT {
+            self.history.undo.remove(0);
+        }
+        // a redo would now overwrite this change
+        self.history.redo.clear();
+    }
+    // Applies blocks sent by the router, which may be changes of other clients,
+    // and writes everything queued for the audit log
+    fn observe(&mut self, blocks: &[HubMessage]) {
+        for block in blocks {
+            self.record(block, true);
+            self.update(std::slice::from_ref(block));
+        }
+        self.flush_audit_log();
+    }
+    // The changes a block makes to the current state, a later entry for the
+    // same id replaces an earlier one
+    fn block_diff(&self, block: &HubMessage) -> DumpDiff {
+        let label_diff = |current: &[String], labels: &LabelList| {
+            let mut diffs: Vec<LabelDiff> = Vec::new();
+            for label in &labels.labels {
+                match (diffs.iter_mut().find(|diff| diff.id == label.index), current.get(label.index)) {
+                    (Some(diff), _) => diff.to.clone_from(&label.name),
+                    (None, Some(from)) => diffs.push(LabelDiff {
+                        id: label.index,
+                        from: from.clone(),
+                        to: label.name.clone(),
+                    }),
+                    (None, None) => continue,
+                }
+            }
+            diffs.retain(|diff| diff.from != diff.to);
+            diffs
+        };
+        match block {
+            HubMessage::InputLabels(labels) => DumpDiff {
+                sources: label_diff(&self.input_labels, labels),
+                ..Default::default()
+            },
+            HubMessage::OutputLabels(labels) => DumpDiff {
+                destinations: label_diff(&self.output_labels, labels),
+                ..Default::default()
+            },
+            HubMessage::VideoRouting(routing) => {
+                let mut routes: Vec<RouteDiff> = Vec::new();
+                for route in &routing.routes {
+                    match (
+                        routes.iter_mut().find(|diff| diff.destination_id == route.destination),
+                        self.video_routes.get(route.destination),
+                    ) {
+                        (Some(diff), _) => diff.to = route.source,
+                        (None, Some(from)) => routes.push(RouteDiff {
+                            destination_id: route.destination,
+                            from: *from,
+                            to: route.source,
+                        }),
+                        (None, None) => continue,
+                    }
+                }
+                routes.retain(|diff| diff.from != diff.to);
+                DumpDiff {
+                    routes,
+                    ..Default::default()
+                }
+            }
+            _ => DumpDiff::default(),
+        }
+    }
+}
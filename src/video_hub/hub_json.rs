@@ -1,58 +1,551 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 use anyhow::Context;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Version of the dump file format written by this version of hub_util.
+///
+/// - 1: unversioned dumps, `time`/`timestamp` and `sourceId`/`routeId` were both in use
+/// - 2: adds `formatVersion`
+/// - 3: adds `idBase`, ids were always 0-based before
+pub const DUMP_FORMAT_VERSION: u64 = 3;
+
+/// Maximum number of labels or routes sent in a single block during an import.
+pub const IMPORT_BATCH_SIZE: usize = 32;
+
+/// How long the router has to stay quiet after an import before its state is checked.
+pub const IMPORT_ECHO_WAIT: Duration = Duration::from_millis(200);
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct VideoHubDump {
-    time: u128,
-    name: String,
-    sources: Vec<VideoHubLabel>,
-    destinations: Vec<VideoHubLabel>,
-    routes: Vec<VideoHubRoute>,
+    /// Version of the dump file format
+    pub format_version: u64,
+    /// Unix time in milliseconds when the dump was created
+    pub time: u128,
+    /// Number of the first input and output, 0 as in the Videohub protocol or 1 as on control panels
+    #[schemars(range(max = 1))]
+    pub id_base: usize,
+    /// Model name of the Videohub the dump was created from
+    pub name: String,
+    pub sources: Vec<VideoHubLabel>,
+    pub destinations: Vec<VideoHubLabel>,
+    pub routes: Vec<VideoHubRoute>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
 pub struct VideoHubLabel {
-    id: usize,
-    name: String,
+    /// Number of the input or output, counted from `idBase`
+    pub id: usize,
+    pub name: String,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum VideoHubLabelType {
     Input,
     Output,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct VideoHubRoute {
-    destination_id: usize,
-    source_id: usize,
+    pub destination_id: usize,
+    pub source_id: usize,
+}
+
+/// A set of ids, parsed from `all`, `none` or a list of ids and ranges such as `0-7,10,12-14`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum IdSelection {
+    #[default]
+    All,
+    None,
+    Only(Vec<RangeInclusive<usize>>),
+}
+
+impl IdSelection {
+    pub fn contains(&self, id: usize) -> bool {
+        match self {
+            IdSelection::All => true,
+            IdSelection::None => false,
+            IdSelection::Only(ranges) => ranges.iter().any(|range| range.contains(&id)),
+        }
+    }
+    /// Renumbers the selected ids from `id_base` to 0-based ids.
+    pub fn to_zero_based(&self, id_base: usize) -> anyhow::Result<IdSelection> {
+        match self {
+            IdSelection::Only(ranges) => ranges
+                .iter()
+                .map(|range| match (range.start().checked_sub(id_base), range.end().checked_sub(id_base)) {
+                    (Some(start), Some(end)) => Ok(start..=end),
+                    _ => Err(anyhow!("Ids start at {}", id_base)),
+                })
+                .collect::<anyhow::Result<_>>()
+                .map(IdSelection::Only),
+            selection => Ok(selection.clone()),
+        }
+    }
+}
+
+impl FromStr for IdSelection {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "all" => return Ok(IdSelection::All),
+            "none" => return Ok(IdSelection::None),
+            _ => {}
+        }
+        s.split(',')
+            .map(|part| {
+                let (start, end) = part.split_once('-').unwrap_or((part, part));
+                let (start, end): (usize, usize) = (start.trim().parse()?, end.trim().parse()?);
+                if start > end {
+                    return Err(anyhow!("Invalid id range {}", part));
+                }
+                Ok(start..=end)
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(IdSelection::Only)
+    }
+}
+
+#[test]
+fn test_id_selection_parse() {
+    assert_eq!("all".parse::<IdSelection>().expect("Failed to parse"), IdSelection::All);
+    assert_eq!("none".parse::<IdSelection>().expect("Failed to parse"), IdSelection::None);
+
+    let selection: IdSelection = "1-8, 10".parse().expect("Failed to parse");
+    assert_eq!(selection, IdSelection::Only(vec![1..=8, 10..=10]));
+    assert!(selection.contains(8));
+    assert!(!selection.contains(9));
+    assert_eq!(selection.to_zero_based(1).expect("Failed to convert"), IdSelection::Only(vec![0..=7, 9..=9]));
+
+    assert!("8-1".parse::<IdSelection>().is_err());
+    assert!("1,x".parse::<IdSelection>().is_err());
+    assert!("0-3".parse::<IdSelection>().expect("Failed to parse").to_zero_based(1).is_err());
+}
+
+/// Error returned when an import failed part way and the changes sent so far were undone.
+#[derive(Debug)]
+pub struct ImportRollback {
+    /// Why the import failed
+    pub cause: anyhow::Error,
+    /// Changes sent to restore the state from before the import, with 0-based ids
+    pub rolled_back: DumpDiff,
+    /// Set if restoring failed as well, the router is then left half configured
+    pub rollback_error: Option<anyhow::Error>,
+}
+
+impl std::fmt::Display for ImportRollback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.rollback_error {
+            None => write!(f, "Import failed and was rolled back: {:#}\n{}", self.cause, self.rolled_back),
+            Some(e) => write!(f, "Import failed and rolling back failed too: {:#}\nRollback error: {:#}", self.cause, e),
+        }
+    }
+}
+
+impl std::error::Error for ImportRollback {}
+
+/// Ids, 0-based, of the labels and routes that did not end up as requested by an import.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportMismatch {
+    pub input_labels: Vec<usize>,
+    pub output_labels: Vec<usize>,
+    pub routes: Vec<usize>,
+}
+
+impl ImportMismatch {
+    pub fn is_empty(&self) -> bool {
+        self.input_labels.is_empty() && self.output_labels.is_empty() && self.routes.is_empty()
+    }
+}
+
+impl std::fmt::Display for ImportMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Videohub state does not match the dump")?;
+        let sections = [
+            ("input labels", &self.input_labels),
+            ("output labels", &self.output_labels),
+            ("routes", &self.routes),
+        ];
+        for (name, ids) in sections.iter().filter(|(_, ids)| !ids.is_empty()) {
+            let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+            write!(f, ", {}: {}", name, ids.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ImportMismatch {}
+
+/// Selects which labels and routes of a dump are imported, routes are selected by destination.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportFilter {
+    pub sources: IdSelection,
+    pub destinations: IdSelection,
+    pub routes: IdSelection,
+}
+
+impl VideoHubDump {
+    /// Parses a dump of any known format version, migrating it to the current one.
+    pub fn from_json(json: &str) -> anyhow::Result<VideoHubDump> {
+        VideoHubDump::from_value(serde_json::from_str(json)?)
+    }
+    // Shared by all formats that carry the full dump, so they are migrated the same way
+    fn from_value(mut value: Value) -> anyhow::Result<VideoHubDump> {
+        VideoHubDump::migrate(&mut value)?;
+        serde_json::from_value(value).with_context(|| "Dump does not match the dump format")
+    }
+    /// Migrates a dump of any known format version to the current one in place.
+    pub fn migrate(value: &mut Value) -> anyhow::Result<()> {
+        let version = match value.get("formatVersion") {
+            None => 1,
+            Some(version) => version
+                .as_u64()
+                .ok_or_else(|| anyhow!("formatVersion must be a positive integer"))?,
+        };
+        if version > DUMP_FORMAT_VERSION {
+            return Err(anyhow!(
+                "Dump format version {} is newer than the supported version {}",
+                version,
+                DUMP_FORMAT_VERSION
+            ));
+        }
+
+        if version < 2 {
+            migrate_v1(value)?;
+        }
+        if version < 3 {
+            migrate_v2(value)?;
+        }
+        Ok(())
+    }
+    /// Renumbers every id in the dump to start at `id_base`.
+    pub fn with_id_base(mut self, id_base: usize) -> anyhow::Result<VideoHubDump> {
+        if id_base > 1 {
+            return Err(anyhow!("Id base must be 0 or 1, got {}", id_base));
+        }
+        let from = self.id_base;
+        let convert = |id: usize| {
+            (id + id_base)
+                .checked_sub(from)
+                .ok_or_else(|| anyhow!("Id {} is below the id base {} of the dump", id, from))
+        };
+
+        for label in self.sources.iter_mut().chain(self.destinations.iter_mut()) {
+            label.id = convert(label.id)?;
+        }
+        for route in &mut self.routes {
+            route.destination_id = convert(route.destination_id)?;
+            route.source_id = convert(route.source_id)?;
+        }
+        self.id_base = id_base;
+        Ok(self)
+    }
+    /// JSON Schema of the current dump format, `schema.json` is generated from it.
+    pub fn json_schema() -> Value {
+        serde_json::to_value(schemars::schema_for!(VideoHubDump)).expect("Schema is always valid JSON")
+    }
+    /// Keeps only the labels and routes selected by `filter`, which uses the id base of the dump.
+    pub fn filter(mut self, filter: &ImportFilter) -> VideoHubDump {
+        self.sources.retain(|label| filter.sources.contains(label.id));
+        self.destinations.retain(|label| filter.destinations.contains(label.id));
+        self.routes.retain(|route| filter.routes.contains(route.destination_id));
+        self
+    }
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        serde_json::to_string_pretty(self).with_context(|| "Failed to create JSON dump")
+    }
+}
+
+// Version 1 dumps were documented with `timestamp` and `routeId` while hub_util
+// wrote `time` and `sourceId`, both spellings are accepted
+fn migrate_v1(value: &mut Value) -> anyhow::Result<()> {
+    let dump = value
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Dump must be a JSON object"))?;
+
+    if let Some(timestamp) = dump.remove("timestamp") {
+        dump.entry("time").or_insert(timestamp);
+    }
+    if let Some(Value::Array(routes)) = dump.get_mut("routes") {
+        for route in routes.iter_mut().filter_map(|route| route.as_object_mut()) {
+            if let Some(source_id) = route.remove("routeId") {
+                route.entry("sourceId").or_insert(source_id);
+            }
+        }
+    }
+    dump.insert("formatVersion".to_string(), Value::from(2));
+    Ok(())
+}
+
+// Ids were always 0-based before version 3
+fn migrate_v2(value: &mut Value) -> anyhow::Result<()> {
+    let dump = value
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Dump must be a JSON object"))?;
+
+    dump.entry("idBase").or_insert(Value::from(0));
+    dump.insert("formatVersion".to_string(), Value::from(3));
+    Ok(())
+}
+
+#[test]
+fn test_dump_migrate_v1() {
+    let dump = VideoHubDump::from_json(
+        r#"{
+    "timestamp": 1741618011000,
+    "name": "Smart Videohub 20 x 20",
+    "sources": [{ "id": 0, "name": "Input 1" }],
+    "destinations": [{ "id": 0, "name": "Output 1" }],
+    "routes": [{ "destinationId": 0, "routeId": 0 }]
+}"#,
+    )
+    .expect("Failed to migrate dump");
+    assert_eq!(dump.format_version, DUMP_FORMAT_VERSION);
+    assert_eq!(dump.time, 1741618011000);
+    assert_eq!(dump.id_base, 0);
+    assert_eq!(dump.routes, vec![VideoHubRoute { destination_id: 0, source_id: 0 }]);
+}
+
+#[test]
+fn test_dump_schema_is_up_to_date() {
+    let schema: Value = serde_json::from_str(include_str!("../../schema.json")).expect("Failed to parse schema.json");
+    assert_eq!(schema, VideoHubDump::json_schema(), "schema.json is outdated, run `hub_util schema > schema.json`");
+}
+
+#[test]
+fn test_dump_rejects_newer_version() {
+    let result = VideoHubDump::from_json(
+        r#"{"formatVersion": 99, "time": 0, "name": "", "sources": [], "destinations": [], "routes": []}"#,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_dump_round_trip() {
+    let dump = VideoHubDump {
+        format_version: DUMP_FORMAT_VERSION,
+        time: 1742323854265,
+        id_base: 0,
+        name: "test".to_string(),
+        sources: vec![VideoHubLabel { id: 0, name: "Src 1".to_string() }],
+        destinations: vec![VideoHubLabel { id: 0, name: "Dest 1".to_string() }],
+        routes: vec![VideoHubRoute { destination_id: 0, source_id: 0 }],
+    };
+    let json = dump.to_json().expect("Failed to serialize dump");
+    assert!(json.contains(&format!("\"formatVersion\": {}", DUMP_FORMAT_VERSION)));
+    assert_eq!(VideoHubDump::from_json(&json).expect("Failed to parse dump"), dump);
+}
+
+#[test]
+fn test_import_mismatch_display() {
+    let mismatch = ImportMismatch {
+        input_labels: vec![1, 3],
+        output_labels: vec![],
+        routes: vec![0],
+    };
+    assert_eq!(mismatch.to_string(), "Videohub state does not match the dump, input labels: 1 3, routes: 0");
+    assert!(ImportMismatch::default().is_empty());
+}
+
+#[test]
+fn test_dump_filter() {
+    let dump = VideoHubDump {
+        format_version: DUMP_FORMAT_VERSION,
+        time: 0,
+        id_base: 0,
+        name: "test".to_string(),
+        sources: vec![VideoHubLabel { id: 0, name: "Src 1".to_string() }],
+        destinations: (0..4).map(|id| VideoHubLabel { id, name: format!("Dest {}", id + 1) }).collect(),
+        routes: (0..4).map(|id| VideoHubRoute { destination_id: id, source_id: 0 }).collect(),
+    };
+    let filter = ImportFilter {
+        sources: IdSelection::None,
+        destinations: IdSelection::All,
+        routes: IdSelection::Only(vec![1..=2]),
+    };
+    let filtered = dump.filter(&filter);
+    assert!(filtered.sources.is_empty());
+    assert_eq!(filtered.destinations.len(), 4);
+    assert_eq!(
+        filtered.routes,
+        vec![
+            VideoHubRoute { destination_id: 1, source_id: 0 },
+            VideoHubRoute { destination_id: 2, source_id: 0 }
+        ]
+    );
+}
+
+#[test]
+fn test_dump_with_id_base() {
+    let dump = VideoHubDump {
+        format_version: DUMP_FORMAT_VERSION,
+        time: 0,
+        id_base: 1,
+        name: "test".to_string(),
+        sources: vec![VideoHubLabel { id: 1, name: "Src 1".to_string() }],
+        destinations: vec![VideoHubLabel { id: 2, name: "Dest 2".to_string() }],
+        routes: vec![VideoHubRoute { destination_id: 2, source_id: 1 }],
+    };
+    let converted = dump.clone().with_id_base(0).expect("Failed to convert dump");
+    assert_eq!(converted.id_base, 0);
+    assert_eq!(converted.sources[0].id, 0);
+    assert_eq!(converted.routes, vec![VideoHubRoute { destination_id: 1, source_id: 0 }]);
+    assert_eq!(converted.with_id_base(1).expect("Failed to convert dump"), dump);
+
+    let mut broken = dump;
+    broken.sources[0].id = 0;
+    assert!(broken.with_id_base(0).is_err());
 }
 
 impl VideoHub {
     pub fn import_dump(&mut self, json: &str) -> anyhow::Result<()> {
-        let dump: VideoHubDump = serde_json::from_str(json)?;
+        let dump = VideoHubDump::from_json(json)?;
+        self.import(dump)
+    }
+    pub fn import(&mut self, dump: VideoHubDump) -> anyhow::Result<()> {
+        self.import_filtered(dump, &ImportFilter::default())
+    }
+    /// Imports the parts of a dump selected by `filter`, which uses 0-based ids.
+    ///
+    /// Only labels and routes that differ from the current state are sent, in
+    /// blocks of at most [`IMPORT_BATCH_SIZE`] lines. Afterwards the changed
+    /// sections are queried from the router and compared with the dump. If a
+    /// block is refused or the router does not end up in the requested state,
+    /// every block sent so far is undone and an [`ImportRollback`] is returned,
+    /// its cause is an [`ImportMismatch`] if the state did not match.
+    pub fn import_filtered(&mut self, dump: VideoHubDump, filter: &ImportFilter) -> anyhow::Result<()> {
+        let expected = self.prepare_import(dump, filter)?;
+        // only what differs is sent, so panels are not flooded with changes
+        let diff = self.diff(expected.clone(), &ImportFilter::default())?;
+        let mut applied = DumpDiff::default();
+        for batch in diff.batches() {
+            let result = self.apply_batch(&batch);
+            applied.append(batch);
+            if let Err(e) = result {
+                return Err(self.roll_back(applied, e).into());
+            }
+        }
+
+        if applied.is_empty() {
+            return Ok(());
+        }
+        if let Err(e) = self.wait_for_echoes().and_then(|_| self.query_sections(&applied)) {
+            return Err(self.roll_back(applied, e).into());
+        }
+        let mismatch = self.mismatch(&expected);
+        if !mismatch.is_empty() {
+            return Err(self.roll_back(applied, mismatch.into()).into());
+        }
+
+        Ok(())
+    }
+    // The router echoes every block it applied, wait until it has nothing more to say
+    fn wait_for_echoes(&mut self) -> anyhow::Result<()> {
+        let start = Instant::now();
+        while self.read_pending(IMPORT_ECHO_WAIT)? {
+            if start.elapsed() > IMPORT_ECHO_WAIT * 10 {
+                return Err(anyhow!("Videohub kept sending updates after the import"));
+            }
+        }
+        Ok(())
+    }
+    // Asks the router for every section the import changed, so the comparison
+    // does not rely on the state applied after each ACK
+    fn query_sections(&mut self, applied: &DumpDiff) -> anyhow::Result<()> {
+        let mut headers: Vec<String> = applied.batches().iter().map(|batch| batch.message().get_header()).collect();
+        headers.dedup();
+        for header in headers {
+            self.query(&header, IMPORT_ECHO_WAIT * 10)?;
+        }
+        Ok(())
+    }
+    /// Lists the labels and routes of a dump with 0-based ids that differ from the current state.
+    pub fn mismatch(&self, dump: &VideoHubDump) -> ImportMismatch {
+        let labels = |current: &[String], labels: &[VideoHubLabel]| -> Vec<usize> {
+            labels
+                .iter()
+                .filter(|label| current.get(label.id) != Some(&label.name))
+                .map(|label| label.id)
+                .collect()
+        };
+        ImportMismatch {
+            input_labels: labels(self.input_labels(), &dump.sources),
+            output_labels: labels(self.output_labels(), &dump.destinations),
+            routes: dump
+                .routes
+                .iter()
+                .filter(|route| self.video_routes().get(route.destination_id) != Some(&route.source_id))
+                .map(|route| route.destination_id)
+                .collect(),
+        }
+    }
+    fn apply_batch(&mut self, batch: &DumpDiff) -> anyhow::Result<()> {
+        let message = batch.message();
+        let section = match &message {
+            HubMessage::InputLabels(_) => "input labels",
+            HubMessage::OutputLabels(_) => "output labels",
+            _ => "routes",
+        };
+        self.send_message(message).with_context(|| format!("Failed to set {} on Videohub", section))
+    }
+    // Restores the state from before `applied`, including the batch that failed
+    fn roll_back(&mut self, applied: DumpDiff, cause: anyhow::Error) -> ImportRollback {
+        let rolled_back = applied.reversed();
+        // keep going after a failure to restore as much as possible
+        let results: Vec<anyhow::Result<()>> = rolled_back
+            .batches()
+            .into_iter()
+            .map(|batch| self.send_message(batch.message()))
+            .collect();
+        let rollback_error = results.into_iter().find_map(Result::err);
+        ImportRollback {
+            cause,
+            rolled_back,
+            rollback_error,
+        }
+    }
+    // Renumbers and filters a dump and checks that it fits this router
+    fn prepare_import(&self, dump: VideoHubDump, filter: &ImportFilter) -> anyhow::Result<VideoHubDump> {
+        let id_base = dump.id_base;
+        let dump = dump.with_id_base(0)?.filter(filter);
 
         if dump.sources.len() > self.input_count() {
             return Err(anyhow!("Dump contains {} inputs but VideoHub contains {} inputs", dump.sources.len(), self.input_count()));
         }
 
         if dump.destinations.len() > self.output_count() {
-            return Err(anyhow!("Dump contains {} outputs but VideoHub contains {} outputs", dump.sources.len(), self.input_count()));
+            return Err(anyhow!("Dump contains {} outputs but VideoHub contains {} outputs", dump.destinations.len(), self.output_count()));
         }
 
-        self.set_labels(VideoHubLabelType::Input, dump.sources).expect("Failed to set input labels on Videohub");
-        self.set_labels(VideoHubLabelType::Output, dump.destinations).expect("Failed to set output labels on Videohub");
+        for label in dump.sources.iter().chain(&dump.destinations) {
+            check_label(&label.name)?;
+        }
 
-        self.set_routes(dump.routes).expect("Failed to set routes on Videohub");
+        let inputs = dump.sources.iter().map(|label| label.id).chain(dump.routes.iter().map(|route| route.source_id));
+        if let Some(id) = inputs.filter(|id| *id >= self.input_count()).min() {
+            return Err(anyhow!("Dump contains input {} but VideoHub contains {} inputs", id + id_base, self.input_count()));
+        }
 
-        Ok(())
+        let outputs = dump.destinations.iter().map(|label| label.id).chain(dump.routes.iter().map(|route| route.destination_id));
+        if let Some(id) = outputs.filter(|id| *id >= self.output_count()).min() {
+            return Err(anyhow!("Dump contains output {} but VideoHub contains {} outputs", id + id_base, self.output_count()));
+        }
+
+        Ok(dump)
     }
-    pub fn dump_json(&self) -> anyhow::Result<String> {
-        let dump = VideoHubDump {
+    pub fn dump(&self) -> VideoHubDump {
+        VideoHubDump {
+            format_version: DUMP_FORMAT_VERSION,
             time: SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .expect("Time went backwards").as_millis(),
+            id_base: 0,
             name: self.model().to_owned(),
             sources: self.input_labels().iter().enumerate().map(|(i, label)| {
                 VideoHubLabel {
@@ -72,8 +565,9 @@ impl VideoHub {
                     source_id: *source_id,
                 }
             }).collect(),
-        };
-
-        serde_json::to_string_pretty(&dump).with_context(|| "Failed to create JSON dump")
+        }
+    }
+    pub fn dump_json(&self) -> anyhow::Result<String> {
+        self.dump().to_json()
     }
 }
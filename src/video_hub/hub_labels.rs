@@ -0,0 +1,42 @@
+use regex::Regex;
+
+impl VideoHub {
+    pub fn labels(&self, label_type: VideoHubLabelType) -> &[String] {
+        match label_type {
+            VideoHubLabelType::Input => self.input_labels(),
+            VideoHubLabelType::Output => self.output_labels(),
+        }
+    }
+    /// Parses `id name` lines as written by `hub_util label get`, with ids
+    /// starting at `id_base`. Empty lines and lines starting with `#` are skipped.
+    /// The labels are returned with 0-based ids.
+    pub fn parse_label_lines(&self, label_type: VideoHubLabelType, text: &str, id_base: usize) -> anyhow::Result<Vec<VideoHubLabel>> {
+        let count = self.labels(label_type).len();
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(i, line)| {
+                let (id, name) = line.trim_start().split_once(' ').unwrap_or((line.trim(), ""));
+                let id = id
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|id| id.checked_sub(id_base))
+                    .filter(|id| *id < count)
+                    .ok_or_else(|| anyhow!("Invalid id {} on line {}, the router has {} to {}", id, i + 1, id_base, (count + id_base).saturating_sub(1)))?;
+                Ok(VideoHubLabel { id, name: name.to_string() })
+            })
+            .collect()
+    }
+    /// Applies `pattern` to every label and returns the labels it changes, with 0-based ids.
+    /// The replacement can refer to capture groups as `$1` or `${name}`.
+    pub fn replace_labels(&self, label_type: VideoHubLabelType, pattern: &Regex, replacement: &str) -> Vec<VideoHubLabel> {
+        self.labels(label_type)
+            .iter()
+            .enumerate()
+            .filter_map(|(id, label)| {
+                let name = pattern.replace_all(label, replacement);
+                (name != label.as_str()).then(|| VideoHubLabel { id, name: name.into_owned() })
+            })
+            .collect()
+    }
+}
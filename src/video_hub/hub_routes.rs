@@ -0,0 +1,106 @@
+impl VideoHub {
+    /// Resolves a number, a range like `1-8` or a label to 0-based ids, numbers start at `id_base`.
+    pub fn resolve_ids(&self, label_type: VideoHubLabelType, spec: &str, id_base: usize) -> anyhow::Result<Vec<usize>> {
+        let kind = match label_type {
+            VideoHubLabelType::Input => "input",
+            VideoHubLabelType::Output => "output",
+        };
+        resolve_ids(spec, self.labels(label_type), id_base, kind)
+    }
+    /// Turns `dest=src` pairs into routes. Each side is a number, a range like
+    /// `1-8` or a label name, numbers start at `id_base`. A range of destinations
+    /// takes either a range of sources of the same length or a single source.
+    pub fn parse_routes(&self, specs: &[String], id_base: usize) -> anyhow::Result<Vec<VideoHubRoute>> {
+        let mut routes = Vec::new();
+        for spec in specs {
+            let (destinations, sources) = spec
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid route {}, expected destination=source", spec))?;
+            let destinations = resolve_ids(destinations, self.output_labels(), id_base, "output")?;
+            let sources = resolve_ids(sources, self.input_labels(), id_base, "input")?;
+
+            if sources.len() != 1 && sources.len() != destinations.len() {
+                return Err(anyhow!(
+                    "Invalid route {}, {} outputs cannot take {} inputs",
+                    spec,
+                    destinations.len(),
+                    sources.len()
+                ));
+            }
+            for (i, destination_id) in destinations.iter().enumerate() {
+                routes.push(VideoHubRoute {
+                    destination_id: *destination_id,
+                    source_id: sources[if sources.len() == 1 { 0 } else { i }],
+                });
+            }
+        }
+        Ok(routes)
+    }
+    /// Checks the numbers and ranges of route specs against the size of the
+    /// router. Labels are left alone, they are resolved when the routes are set.
+    pub fn check_route_ids(&self, specs: &[String], id_base: usize) -> anyhow::Result<()> {
+        for spec in specs {
+            let (destinations, sources) = spec
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid route {}, expected destination=source", spec))?;
+            if id_range(destinations).is_some() {
+                resolve_ids(destinations, self.output_labels(), id_base, "output")?;
+            }
+            if id_range(sources).is_some() {
+                resolve_ids(sources, self.input_labels(), id_base, "input")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The ids of a number or range such as `3` or `1-4`, `None` for a label
+fn id_range(spec: &str) -> Option<RangeInclusive<usize>> {
+    let spec = spec.trim();
+    let numbers = spec
+        .split_once('-')
+        .map(|(start, end)| (start.trim().parse::<usize>(), end.trim().parse::<usize>()));
+    match (spec.parse::<usize>(), numbers) {
+        (Ok(id), _) => Some(id..=id),
+        (_, Some((Ok(start), Ok(end)))) => Some(start..=end),
+        _ => None,
+    }
+}
+
+// Resolves a number, range or label to 0-based ids, labels are matched exactly
+// first and then ignoring case
+fn resolve_ids(spec: &str, labels: &[String], id_base: usize, kind: &str) -> anyhow::Result<Vec<usize>> {
+    let spec = spec.trim();
+    if let Some(range) = id_range(spec) {
+        let (start, end) = (*range.start(), *range.end());
+        if start > end || start < id_base || end >= labels.len() + id_base {
+            return Err(anyhow!(
+                "Invalid {} {}, the router has {} to {}",
+                kind,
+                spec,
+                id_base,
+                (labels.len() + id_base).saturating_sub(1)
+            ));
+        }
+        return Ok((start - id_base..=end - id_base).collect());
+    }
+
+    labels
+        .iter()
+        .position(|label| label == spec)
+        .or_else(|| labels.iter().position(|label| label.eq_ignore_ascii_case(spec)))
+        .map(|id| vec![id])
+        .ok_or_else(|| anyhow!("No {} is labelled {}", kind, spec))
+}
+
+#[test]
+fn test_resolve_ids() {
+    let labels: Vec<String> = ["Camera 1", "Camera 2", "Cam-3", "Graphics"].iter().map(|label| label.to_string()).collect();
+    assert_eq!(resolve_ids("2", &labels, 0, "input").expect("Failed to resolve"), vec![2]);
+    assert_eq!(resolve_ids("1-3", &labels, 1, "input").expect("Failed to resolve"), vec![0, 1, 2]);
+    assert_eq!(resolve_ids("Cam-3", &labels, 0, "input").expect("Failed to resolve"), vec![2]);
+    assert_eq!(resolve_ids("graphics", &labels, 0, "input").expect("Failed to resolve"), vec![3]);
+    assert!(resolve_ids("0", &labels, 1, "input").is_err());
+    assert!(resolve_ids("3-5", &labels, 0, "input").is_err());
+    assert!(resolve_ids("Camera 3", &labels, 0, "input").is_err());
+}
@@ -0,0 +1,78 @@
+impl VideoHub {
+    /// Stages a route with 0-based ids to be sent by [`VideoHub::take`], replacing
+    /// a route staged earlier for the same destination.
+    pub fn stage_route(&mut self, destination: usize, source: usize) -> anyhow::Result<()> {
+        if destination >= self.output_count() || source >= self.input_count() {
+            return Err(anyhow!(
+                "Cannot route input {} to output {}, the router has {} inputs and {} outputs",
+                source,
+                destination,
+                self.input_count(),
+                self.output_count()
+            ));
+        }
+        self.pending_routes.insert(destination, source);
+        Ok(())
+    }
+    pub fn stage_routes(&mut self, routes: &[VideoHubRoute]) -> anyhow::Result<()> {
+        for route in routes {
+            self.stage_route(route.destination_id, route.source_id)?;
+        }
+        Ok(())
+    }
+    /// Routes staged for the next take, by destination.
+    pub fn pending_routes(&self) -> Vec<VideoHubRoute> {
+        self.pending_routes
+            .iter()
+            .map(|(destination, source)| VideoHubRoute {
+                destination_id: *destination,
+                source_id: *source,
+            })
+            .collect()
+    }
+    /// Compares the staged routes with the current routing, with 0-based ids.
+    pub fn pending_diff(&self) -> DumpDiff {
+        let routes: Vec<RouteDiff> = self
+            .pending_routes
+            .iter()
+            .filter(|(destination, source)| self.video_routes[**destination] != **source)
+            .map(|(destination, source)| RouteDiff {
+                destination_id: *destination,
+                from: self.video_routes[*destination],
+                to: *source,
+            })
+            .collect();
+        let locked = routes
+            .iter()
+            .map(|route| route.destination_id)
+            .filter(|destination| self.output_locks[*destination] == LockState::Locked)
+            .collect();
+        DumpDiff {
+            routes,
+            locked,
+            ..Default::default()
+        }
+    }
+    /// Sends the staged routes that differ from the current routing as a single
+    /// routing block and clears them. They stay staged if the router refuses them.
+    pub fn take(&mut self) -> anyhow::Result<DumpDiff> {
+        let diff = self.pending_diff();
+        if !diff.routes.is_empty() {
+            let routes = diff
+                .routes
+                .iter()
+                .map(|route| VideoHubRoute {
+                    destination_id: route.destination_id,
+                    source_id: route.to,
+                })
+                .collect();
+            self.set_routes(routes).with_context(|| "Failed to take pending routes")?;
+        }
+        self.pending_routes.clear();
+        Ok(diff)
+    }
+    /// Discards the staged routes.
+    pub fn cancel(&mut self) {
+        self.pending_routes.clear();
+    }
+}
@@ -2,32 +2,104 @@
 use anyhow::anyhow;
 
 use crate::{debug_println, read_to_newline};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
 use std::net::{SocketAddr, SocketAddrV4, TcpStream};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub struct VideoHub {
     stream: TcpStream,
     model: String,
+    unique_id: String,
     input_count: usize,
     input_labels: Vec<String>,
     output_count: usize,
     output_labels: Vec<String>,
     video_routes: Vec<usize>,
+    output_locks: Vec<LockState>,
+    input_status: Vec<String>,
+    command_stats: CommandStats,
+    changes: Vec<HubChange>,
+    // positions in `changes` of the changes made by requests of this client
+    local_changes: Vec<usize>,
+    // routes staged for `take`, by destination
+    pending_routes: BTreeMap<usize, usize>,
+    history: History,
+    audit_log: Option<AuditLog>,
+}
+
+/// Lock state of a destination as seen by this client.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockState {
+    #[default]
+    Unlocked,
+    /// Locked by this client
+    Owned,
+    /// Locked by another client
+    Locked,
+}
+
+impl LockState {
+    /// Lowercase name as used in JSON and CSV output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LockState::Unlocked => "unlocked",
+            LockState::Owned => "owned",
+            LockState::Locked => "locked",
+        }
+    }
+}
+
+/// Counters for the requests sent to a Videohub during a session.
+#[derive(Debug, Clone, Default)]
+pub struct CommandStats {
+    pub sent: u64,
+    pub nacks: u64,
+    pub latency_total: Duration,
+    pub last_latency: Duration,
+}
+
+/// A single change to the state of a Videohub, either requested by this client
+/// or reported by the router after another client changed it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HubChange {
+    InputLabel { index: usize, name: String },
+    OutputLabel { index: usize, name: String },
+    Route { destination: usize, source: usize },
+    Lock { destination: usize, state: LockState },
+    InputStatus { index: usize, status: String },
 }
 
 include!("hub_json.rs");
+include!("hub_diff.rs");
+include!("hub_formats.rs");
+include!("hub_routes.rs");
+include!("hub_labels.rs");
+include!("hub_take.rs");
+include!("hub_history.rs");
+include!("hub_audit.rs");
 
 impl VideoHub {
     fn default(tcp_stream: TcpStream) -> Self {
         Self {
             stream: tcp_stream,
             model: "".to_string(),
+            unique_id: "".to_string(),
             input_count: 0,
             input_labels: vec![],
             output_count: 0,
             output_labels: vec![],
             video_routes: vec![],
+            output_locks: vec![],
+            input_status: vec![],
+            command_stats: CommandStats::default(),
+            changes: vec![],
+            local_changes: vec![],
+            pending_routes: BTreeMap::new(),
+            history: History::default(),
+            audit_log: None,
         }
     }
     pub fn input_count(&self) -> usize {
@@ -45,15 +117,29 @@ impl VideoHub {
     pub fn model(&self) -> &str {
         &self.model
     }
+    pub fn unique_id(&self) -> &str {
+        &self.unique_id
+    }
     pub fn video_routes(&self) -> &Vec<usize> {
         &self.video_routes
     }
+    pub fn output_locks(&self) -> &Vec<LockState> {
+        &self.output_locks
+    }
+    /// Connector type of each input as reported by the router, e.g. `BNC` or `None`
+    pub fn input_status(&self) -> &Vec<String> {
+        &self.input_status
+    }
+    pub fn command_stats(&self) -> &CommandStats {
+        &self.command_stats
+    }
     pub fn set_label(
         &mut self,
         label_type: VideoHubLabelType,
         index: usize,
         label: &str,
     ) -> anyhow::Result<()> {
+        check_label(label)?;
         let labels = LabelList {
             labels: vec![Label {
                 index,
@@ -71,6 +157,9 @@ impl VideoHub {
         label_type: VideoHubLabelType,
         labels: Vec<VideoHubLabel>,
     ) -> anyhow::Result<()> {
+        for label in &labels {
+            check_label(&label.name)?;
+        }
         let labels = LabelList {
             labels: labels
                 .iter()
@@ -98,6 +187,26 @@ impl VideoHub {
 
         self.send_message(HubMessage::VideoRouting(routes))
     }
+    /// Locks a destination for this client, or releases a lock it holds.
+    pub fn set_lock(&mut self, destination: usize, lock: bool) -> anyhow::Result<()> {
+        let state = if lock { LockState::Owned } else { LockState::Unlocked };
+        self.send_message(HubMessage::OutputLocks(OutputLocks {
+            locks: vec![Lock { destination, state }],
+        }))
+    }
+    /// Checks that the router is still responding.
+    pub fn ping(&mut self) -> anyhow::Result<()> {
+        self.send_message(HubMessage::Ping)
+    }
+}
+
+// Rejects labels with a line break, which would end the line or block they
+// are sent in and let the rest of the label be read as another command
+fn check_label(label: &str) -> anyhow::Result<()> {
+    if label.contains(['\r', '\n']) {
+        return Err(anyhow!("Label {:?} contains a line break", label));
+    }
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -107,10 +216,13 @@ enum HubMessage {
     InputLabels(LabelList),
     OutputLabels(LabelList),
     VideoRouting(VideoRouting),
+    OutputLocks(OutputLocks),
+    InputStatus(LabelList),
+    Ping,
     PreludeEnd,
     Acknowledge,
     NoAcknowledge,
-    TODO,
+    Todo,
 }
 
 #[derive(Debug, Default)]
@@ -150,6 +262,17 @@ struct Route {
     source: usize,
 }
 
+#[derive(Default, Debug)]
+struct OutputLocks {
+    locks: Vec<Lock>,
+}
+
+#[derive(Debug)]
+struct Lock {
+    destination: usize,
+    state: LockState,
+}
+
 impl Preamble {
     fn parse(lines: &Vec<&str>) -> anyhow::Result<HubMessage> {
         let mut preamble: Preamble = Preamble::default();
@@ -363,6 +486,59 @@ fn test_video_routing_serialize() {
     assert_eq!(serialized, "0 0\n1 1\n");
 }
 
+impl OutputLocks {
+    // Example format:
+    // 0 U (output 0 unlocked)
+    // 1 O (output 1 locked by this client)
+    // 2 L (output 2 locked by another client)
+    // ...
+    fn parse(lines: &Vec<&str>) -> anyhow::Result<HubMessage> {
+        let mut output_locks = OutputLocks::default();
+        for line in lines {
+            let (dest, state) = line
+                .split_once(' ')
+                .ok_or_else(|| anyhow!("Malformed lock line: {}", line))?;
+
+            let state = match state {
+                "U" => LockState::Unlocked,
+                "O" => LockState::Owned,
+                "L" => LockState::Locked,
+                _ => return Err(anyhow!("Unknown lock state: {}", state)),
+            };
+
+            output_locks.locks.push(Lock {
+                destination: dest.parse()?,
+                state,
+            });
+        }
+        Ok(HubMessage::OutputLocks(output_locks))
+    }
+    fn serialize(&self) -> String {
+        let mut serialized = String::new();
+        for lock in &self.locks {
+            let state = match lock.state {
+                LockState::Unlocked => "U",
+                LockState::Owned => "O",
+                LockState::Locked => "L",
+            };
+            serialized += &format!("{} {}\n", lock.destination, state);
+        }
+        serialized
+    }
+}
+
+#[test]
+fn test_output_locks_parse() {
+    let msg = OutputLocks::parse(&vec!["0 U", "1 O", "2 L"]).expect("Failed to parse output locks");
+    if let HubMessage::OutputLocks(output_locks) = msg {
+        assert_eq!(output_locks.locks.len(), 3);
+        assert_eq!(output_locks.locks[1].state, LockState::Owned);
+        assert_eq!(output_locks.locks[2].state, LockState::Locked);
+    } else {
+        panic!("Parsed message is not output locks");
+    }
+}
+
 impl HubMessage {
     pub fn get_header(&self) -> String {
         match self {
@@ -371,6 +547,9 @@ impl HubMessage {
             HubMessage::InputLabels(_) => "INPUT LABELS:".to_string(),
             HubMessage::OutputLabels(_) => "OUTPUT LABELS:".to_string(),
             HubMessage::VideoRouting(_) => "VIDEO OUTPUT ROUTING:".to_string(),
+            HubMessage::OutputLocks(_) => "VIDEO OUTPUT LOCKS:".to_string(),
+            HubMessage::InputStatus(_) => "VIDEO INPUT STATUS:".to_string(),
+            HubMessage::Ping => "PING:".to_string(),
             _ => "TODO".to_string(),
         }
     }
@@ -379,7 +558,7 @@ impl HubMessage {
         let blocks: Vec<&str> = msg.split("\n\n").collect();
         for block in blocks {
             let lines: Vec<&str> = block.lines().collect();
-            if lines.len() < 1 {
+            if lines.is_empty() {
                 continue;
             }
             let header = lines[0];
@@ -389,11 +568,12 @@ impl HubMessage {
             let hub_message = match header {
                 "PROTOCOL PREAMBLE:" => Preamble::parse(&lines),
                 "VIDEOHUB DEVICE:" => DeviceInfo::parse(&lines),
-                "INPUT LABELS:" => LabelList::parse(&lines).map(|i| HubMessage::InputLabels(i)),
-                "OUTPUT LABELS:" => LabelList::parse(&lines).map(|i| HubMessage::OutputLabels(i)),
-                "VIDEO OUTPUT LOCKS:" => Ok(HubMessage::TODO),
+                "INPUT LABELS:" => LabelList::parse(&lines).map(HubMessage::InputLabels),
+                "OUTPUT LABELS:" => LabelList::parse(&lines).map(HubMessage::OutputLabels),
+                "VIDEO OUTPUT LOCKS:" => OutputLocks::parse(&lines),
+                "VIDEO INPUT STATUS:" => LabelList::parse(&lines).map(HubMessage::InputStatus),
                 "VIDEO OUTPUT ROUTING:" => VideoRouting::parse(&lines),
-                "CONFIGURATION:" => Ok(HubMessage::TODO),
+                "CONFIGURATION:" => Ok(HubMessage::Todo),
                 "END PRELUDE:" => Ok(HubMessage::PreludeEnd),
                 "ACK" => Ok(HubMessage::Acknowledge),
                 "NACK" => Ok(HubMessage::NoAcknowledge),
@@ -424,12 +604,42 @@ impl VideoHub {
     fn read_all(&mut self) -> String {
         read_to_newline(&mut self.stream, None).unwrap_or_default()
     }
+    // Reads blocks until the server acknowledges (or rejects) the last request.
+    // Blocks sent before the acknowledgement (e.g. the echo of an earlier change)
+    // are applied to the local state as they arrive, blocks read together with
+    // the acknowledgement but sent after it are returned separately so they can
+    // be applied after the request itself.
+    fn read_response(&mut self) -> anyhow::Result<(Vec<HubMessage>, Vec<HubMessage>)> {
+        let mut response = Vec::new();
+        loop {
+            let msg = self.read_all();
+            if msg.is_empty() {
+                return Ok((response, Vec::new()));
+            }
+
+            let mut blocks = HubMessage::parse_blocks(&msg)?;
+            let acknowledged = blocks
+                .iter()
+                .position(|x| matches!(x, HubMessage::Acknowledge | HubMessage::NoAcknowledge));
+            let after = match acknowledged {
+                Some(position) => blocks.split_off(position + 1),
+                None => Vec::new(),
+            };
+            self.observe(&blocks);
+            response.extend(blocks);
+            if acknowledged.is_some() {
+                return Ok((response, after));
+            }
+        }
+    }
 
     fn send_message(&mut self, msg: HubMessage) -> anyhow::Result<()> {
         let serialized = match &msg {
             HubMessage::InputLabels(labels) => Ok(labels.serialize()),
             HubMessage::OutputLabels(labels) => Ok(labels.serialize()),
             HubMessage::VideoRouting(routes) => Ok(routes.serialize()),
+            HubMessage::OutputLocks(locks) => Ok(locks.serialize()),
+            HubMessage::Ping => Ok(String::new()),
             _ => Err(anyhow!("Cannot serialize this type")),
         }?;
 
@@ -438,26 +648,103 @@ impl VideoHub {
         // header does not contain newline, and message must be terminated with 2 newlines
         let serialized = format!("{}\n{}\n", header, serialized);
 
+        let start = Instant::now();
         self.write(&serialized)?;
 
-        let response = self.read_all();
-
-        let blocks = HubMessage::parse_blocks(&response)?;
+        let (blocks, after) = self.read_response()?;
 
-        self.update(&blocks)?;
+        self.command_stats.sent += 1;
+        self.command_stats.last_latency = start.elapsed();
+        self.command_stats.latency_total += self.command_stats.last_latency;
 
         // Return an error if server returns 'NACK' or fails to send an 'ACK'
         if blocks
             .iter()
             .any(|x| matches!(x, HubMessage::NoAcknowledge))
-            || !blocks.iter().any(|x| matches!(x, HubMessage::Acknowledge))
         {
-            // return Err(anyhow!("Server did not acknowledge request: {}", response));
+            self.observe(&after);
+            self.command_stats.nacks += 1;
+            return Err(anyhow!("Server rejected request: {}", header));
         }
+        if !blocks.iter().any(|x| matches!(x, HubMessage::Acknowledge)) {
+            return Err(anyhow!("Server did not acknowledge request: {}", header));
+        }
+
+        // The server echoes accepted changes after the 'ACK', apply them now so
+        // the local state does not depend on when the echo is read. The audit
+        // log entries of the request are written together with the echo.
+        self.record(&msg, false);
+        let first_change = self.changes.len();
+        self.update(&[msg]);
+        self.local_changes.extend(first_change..self.changes.len());
+        self.observe(&after);
 
         Ok(())
     }
-    fn update(&mut self, blocks: &Vec<HubMessage>) -> anyhow::Result<()> {
+    // Sends a block without lines, which the router answers with the whole
+    // section, and applies the answer. Changes confirmed by an ACK are applied
+    // locally right away, this replaces them with what the router really holds.
+    fn query(&mut self, header: &str, timeout: Duration) -> anyhow::Result<()> {
+        self.write(&format!("{}\n\n", header))?;
+        let (blocks, mut after) = self.read_response()?;
+        if !blocks.iter().any(|x| matches!(x, HubMessage::Acknowledge)) {
+            return Err(anyhow!("Server did not answer query: {}", header));
+        }
+
+        let start = Instant::now();
+        while !after.iter().any(|block| block.get_header() == header) {
+            if start.elapsed() > timeout {
+                self.observe(&after);
+                return Err(anyhow!("Server did not answer query: {}", header));
+            }
+            let msg = self.read_all();
+            after.extend(HubMessage::parse_blocks(&msg)?);
+        }
+        self.observe(&after);
+        Ok(())
+    }
+    /// Waits up to `timeout` for the router to send new state and returns every
+    /// change applied since the last call, including changes made by this client.
+    pub fn poll(&mut self, timeout: Duration) -> anyhow::Result<Vec<HubChange>> {
+        self.read_pending(timeout)?;
+        self.local_changes.clear();
+        Ok(std::mem::take(&mut self.changes))
+    }
+    /// Number of changes applied since the last [`VideoHub::poll`].
+    pub fn pending_change_count(&self) -> usize {
+        self.changes.len()
+    }
+    /// Drops the changes made by requests of this client after the first `count`
+    /// since the last poll, so they are not returned by the next [`VideoHub::poll`].
+    /// Changes of other clients reported meanwhile are kept.
+    pub fn discard_local_changes_after(&mut self, count: usize) {
+        let (discarded, kept): (Vec<usize>, Vec<usize>) = self.local_changes.iter().partition(|index| **index >= count);
+        for index in discarded.into_iter().rev() {
+            self.changes.remove(index);
+        }
+        self.local_changes = kept;
+    }
+    // Applies whatever the router sends within `timeout`, returns false if it sent nothing
+    fn read_pending(&mut self, timeout: Duration) -> anyhow::Result<bool> {
+        self.stream.set_read_timeout(Some(timeout))?;
+        let mut buf = [0u8; 1];
+        let available = match self.stream.peek(&mut buf) {
+            Ok(0) => Err(anyhow!("Connection closed by VideoHub")),
+            Ok(_) => Ok(true),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(false),
+            Err(e) => Err(e.into()),
+        };
+        self.stream.set_read_timeout(Some(Duration::from_millis(200)))?;
+
+        if !available? {
+            return Ok(false);
+        }
+        let msg = self.read_all();
+        let blocks = HubMessage::parse_blocks(&msg)?;
+        self.observe(&blocks);
+        Ok(true)
+    }
+    fn update(&mut self, blocks: &[HubMessage]) {
         for block in blocks {
             match block {
                 HubMessage::Preamble(preamble) => {
@@ -471,39 +758,105 @@ impl VideoHub {
                     self.input_labels
                         .resize(device_info.input_count, "".to_string());
                     self.output_labels
+                        .resize(device_info.output_count, "".to_string());
+                    self.video_routes.resize(device_info.output_count, 0);
+                    self.output_locks
+                        .resize(device_info.output_count, LockState::Unlocked);
+                    self.input_status
                         .resize(device_info.input_count, "".to_string());
-                    self.video_routes.resize(device_info.input_count, 0);
 
                     self.model = device_info.model.clone();
+                    self.unique_id = device_info.uuid.clone();
                 }
                 HubMessage::InputLabels(input_labels) => {
                     debug_println!("InputLabels: {:?}", input_labels);
                     for label in &input_labels.labels {
-                        self.input_labels[label.index] = label.name.clone();
+                        match self.input_labels.get_mut(label.index) {
+                            Some(name) if *name != label.name => {
+                                name.clone_from(&label.name);
+                                self.changes.push(HubChange::InputLabel {
+                                    index: label.index,
+                                    name: label.name.clone(),
+                                });
+                            }
+                            Some(_) => continue,
+                            None => debug_println!("Input label out of range: {}", label.index),
+                        }
                     }
                 }
                 HubMessage::OutputLabels(output_labels) => {
                     debug_println!("OutputLabels: {:?}", output_labels);
                     for label in &output_labels.labels {
-                        self.output_labels[label.index] = label.name.clone();
+                        match self.output_labels.get_mut(label.index) {
+                            Some(name) if *name != label.name => {
+                                name.clone_from(&label.name);
+                                self.changes.push(HubChange::OutputLabel {
+                                    index: label.index,
+                                    name: label.name.clone(),
+                                });
+                            }
+                            Some(_) => continue,
+                            None => debug_println!("Output label out of range: {}", label.index),
+                        }
                     }
                 }
                 HubMessage::VideoRouting(routing) => {
                     debug_println!("VideoRouting: {:?}", routing);
                     for route in &routing.routes {
-                        self.video_routes[route.destination] = route.source;
+                        match self.video_routes.get_mut(route.destination) {
+                            Some(source) if *source != route.source => {
+                                *source = route.source;
+                                self.changes.push(HubChange::Route {
+                                    destination: route.destination,
+                                    source: route.source,
+                                });
+                            }
+                            Some(_) => continue,
+                            None => debug_println!("Route destination out of range: {}", route.destination),
+                        }
+                    }
+                }
+                HubMessage::OutputLocks(output_locks) => {
+                    debug_println!("OutputLocks: {:?}", output_locks);
+                    for lock in &output_locks.locks {
+                        match self.output_locks.get_mut(lock.destination) {
+                            Some(state) if *state != lock.state => {
+                                *state = lock.state;
+                                self.changes.push(HubChange::Lock {
+                                    destination: lock.destination,
+                                    state: lock.state,
+                                });
+                            }
+                            Some(_) => continue,
+                            None => debug_println!("Lock destination out of range: {}", lock.destination),
+                        }
+                    }
+                }
+                HubMessage::InputStatus(input_status) => {
+                    debug_println!("InputStatus: {:?}", input_status);
+                    for status in &input_status.labels {
+                        match self.input_status.get_mut(status.index) {
+                            Some(current) if *current != status.name => {
+                                current.clone_from(&status.name);
+                                self.changes.push(HubChange::InputStatus {
+                                    index: status.index,
+                                    status: status.name.clone(),
+                                });
+                            }
+                            Some(_) => continue,
+                            None => debug_println!("Input status out of range: {}", status.index),
+                        }
                     }
                 }
                 _ => continue,
             }
         }
-        Ok(())
     }
     pub fn new(addr: SocketAddrV4) -> anyhow::Result<VideoHub> {
         let stream = TcpStream::connect_timeout(&SocketAddr::from(addr), Duration::from_secs(5))?;
         stream.set_read_timeout(Some(Duration::from_millis(200)))?;
 
-        println!("Connected to VideoHub at {}", addr);
+        eprintln!("Connected to VideoHub at {}", addr);
 
         let mut hub = VideoHub::default(stream);
 
@@ -511,7 +864,7 @@ impl VideoHub {
 
         let blocks = HubMessage::parse_blocks(&hello_msg)?;
 
-        if blocks.len() == 0 {
+        if blocks.is_empty() {
             return Err(anyhow::anyhow!("Failed to parse blocks from hello"));
         }
 
@@ -532,7 +885,10 @@ impl VideoHub {
             return Err(anyhow::anyhow!("Failed to find device info block"));
         }
 
-        hub.update(&blocks)?;
+        hub.update(&blocks);
+        // the initial state is not a change
+        hub.changes.clear();
+        hub.local_changes.clear();
 
         Ok(hub)
     }
@@ -0,0 +1,211 @@
+use anyhow::{anyhow, Context};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::video_hub::{HubChange, VideoHub};
+
+/// Prints every change reported by a Videohub with a timestamp, either as
+/// readable lines or as one JSON object per line (NDJSON).
+pub struct Watcher<W: Write> {
+    hub: VideoHub,
+    output: W,
+    ndjson: bool,
+    id_base: usize,
+}
+
+impl<W: Write> Watcher<W> {
+    pub fn new(hub: VideoHub, output: W, ndjson: bool, id_base: usize) -> Watcher<W> {
+        Watcher {
+            hub,
+            output,
+            ndjson,
+            id_base,
+        }
+    }
+    pub fn hub(&self) -> &VideoHub {
+        &self.hub
+    }
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+    /// Waits up to `timeout` for changes and prints them.
+    pub fn step(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        let changes = self.hub.poll(timeout)?;
+        // after the poll, so changes are not stamped with the start of the wait
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        for change in changes {
+            let event = change_event(&self.hub, &change, self.id_base);
+            let line = if self.ndjson {
+                let mut event = event;
+                event["time"] = json!(format_time(time));
+                event.to_string()
+            } else {
+                format!("{} {}", format_time(time), describe_change(&self.hub, &change, self.id_base))
+            };
+            writeln!(self.output, "{}", line)?;
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        loop {
+            self.step(Duration::from_millis(500))?;
+        }
+    }
+}
+
+/// A change as a JSON object with a `type` field and labels resolved, ids start at `id_base`.
+pub fn change_event(hub: &VideoHub, change: &HubChange, id_base: usize) -> Value {
+    let input = |index: usize| hub.input_labels().get(index).cloned().unwrap_or_default();
+    let output = |index: usize| hub.output_labels().get(index).cloned().unwrap_or_default();
+    match change {
+        HubChange::InputLabel { index, name } => json!({"type": "inputLabel", "index": index + id_base, "name": name}),
+        HubChange::OutputLabel { index, name } => json!({"type": "outputLabel", "index": index + id_base, "name": name}),
+        HubChange::Route { destination, source } => json!({
+            "type": "route",
+            "destination": destination + id_base,
+            "destinationLabel": output(*destination),
+            "source": source + id_base,
+            "sourceLabel": input(*source),
+        }),
+        HubChange::Lock { destination, state } => json!({
+            "type": "lock",
+            "destination": destination + id_base,
+            "destinationLabel": output(*destination),
+            "state": state.name(),
+        }),
+        HubChange::InputStatus { index, status } => json!({
+            "type": "inputStatus",
+            "index": index + id_base,
+            "label": input(*index),
+            "status": status,
+        }),
+    }
+}
+
+/// A change as a readable line, ids start at `id_base`.
+pub fn describe_change(hub: &VideoHub, change: &HubChange, id_base: usize) -> String {
+    let input = |index: usize| hub.input_labels().get(index).cloned().unwrap_or_default();
+    let output = |index: usize| hub.output_labels().get(index).cloned().unwrap_or_default();
+    match change {
+        HubChange::InputLabel { index, name } => format!("label input {} {:?}", index + id_base, name),
+        HubChange::OutputLabel { index, name } => format!("label output {} {:?}", index + id_base, name),
+        HubChange::Route { destination, source } => format!(
+            "route {} {} <- {} {}",
+            destination + id_base,
+            output(*destination),
+            source + id_base,
+            input(*source)
+        ),
+        HubChange::Lock { destination, state } => {
+            format!("lock {} {} {}", destination + id_base, output(*destination), state.name())
+        }
+        HubChange::InputStatus { index, status } => {
+            format!("status input {} {} {}", index + id_base, input(*index), status)
+        }
+    }
+}
+
+/// Formats a time since the Unix epoch as an RFC 3339 UTC timestamp with milliseconds.
+pub fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    let (days, seconds_of_day) = ((seconds / 86400) as i64, seconds % 86400);
+
+    // civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        time.subsec_millis()
+    )
+}
+
+// days since 1970-01-01 from a civil date, see http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Parses a UTC timestamp like those of [`format_time`] into a time since the
+/// Unix epoch. The time of day, seconds and milliseconds may be left out.
+pub fn parse_time(s: &str) -> anyhow::Result<Duration> {
+    let (date, time) = match s.split_once(['T', ' ']) {
+        Some((date, time)) => {
+            let time = time
+                .strip_suffix('Z')
+                .ok_or_else(|| anyhow!("Invalid time {}, times must be given in UTC ending with Z", s))?;
+            (date, Some(time))
+        }
+        None => (s, None),
+    };
+    let date = date
+        .split('-')
+        .map(|part| part.parse::<i64>())
+        .collect::<Result<Vec<i64>, _>>()
+        .with_context(|| format!("Invalid date {}", s))?;
+    let days = match date[..] {
+        [year, month, day] if year >= 1970 && (1..=12).contains(&month) && (1..=31).contains(&day) => {
+            days_from_civil(year, month, day) as u64
+        }
+        _ => return Err(anyhow!("Invalid date {}, expected YYYY-MM-DD", s)),
+    };
+
+    let Some(time) = time else {
+        return Ok(Duration::from_secs(days * 86400));
+    };
+    let invalid = || anyhow!("Invalid time {}, expected HH:MM[:SS[.mmm]]", s);
+    let (clock, millis) = match time.split_once('.') {
+        Some((clock, millis)) if millis.len() == 3 => (clock, millis.parse::<u64>().map_err(|_| invalid())?),
+        Some(_) => return Err(invalid()),
+        None => (time, 0),
+    };
+    let parts = clock
+        .split(':')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<Vec<u64>, _>>()
+        .map_err(|_| invalid())?;
+    let seconds = match parts[..] {
+        [hours, minutes] if hours < 24 && minutes < 60 && millis == 0 => hours * 3600 + minutes * 60,
+        [hours, minutes, seconds] if hours < 24 && minutes < 60 && seconds < 60 => {
+            hours * 3600 + minutes * 60 + seconds
+        }
+        _ => return Err(invalid()),
+    };
+    Ok(Duration::from_millis((days * 86400 + seconds) * 1000 + millis))
+}
+
+#[test]
+fn test_format_time() {
+    assert_eq!(format_time(Duration::from_millis(0)), "1970-01-01T00:00:00.000Z");
+    assert_eq!(format_time(Duration::from_millis(951_782_400_123)), "2000-02-29T00:00:00.123Z");
+    assert_eq!(format_time(Duration::from_millis(1_742_323_854_265)), "2025-03-18T18:50:54.265Z");
+}
+
+#[test]
+fn test_parse_time() {
+    assert_eq!(parse_time("2025-03-18T18:50:54.265Z").expect("Failed to parse"), Duration::from_millis(1_742_323_854_265));
+    assert_eq!(parse_time("2000-02-29").expect("Failed to parse"), Duration::from_secs(951_782_400));
+    assert_eq!(parse_time("2000-02-29 00:01Z").expect("Failed to parse"), Duration::from_secs(951_782_460));
+    assert!(parse_time("2000-02-29T00:01").is_err());
+    assert!(parse_time("2000-02-29T00:01:00.5Z").is_err());
+    assert!(parse_time("18:50").is_err());
+}
@@ -0,0 +1,96 @@
+extern crate hub_util;
+
+mod common;
+
+use common::{ack_all_commands, spawn_test_server};
+use hub_util::audit::{read_audit_log, AuditChange, AuditLog, AuditOrigin, AuditQuery};
+use hub_util::read_to_newline;
+use hub_util::video_hub::{IdSelection, LockState, VideoHub, VideoHubRoute};
+use std::fs;
+use std::io::Write;
+use std::net::TcpStream;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn audit_log_does_record_local_and_external_changes() {
+    // a panel routes input 6 to output 3 while hub_util is connected
+    let port = spawn_test_server(Some(|client: &mut TcpStream| {
+        thread::sleep(Duration::from_millis(100));
+        client
+            .write_all("VIDEO OUTPUT ROUTING:\n2 5\n\n".as_bytes())
+            .expect("failed to send");
+        ack_all_commands(client);
+    }));
+    let path = std::env::temp_dir().join(format!("hub_util_audit_{}.jsonl", port));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    hub.set_audit_log(Some(AuditLog::open(&path).expect("failed to open audit log")));
+
+    hub.set_routes(vec![VideoHubRoute { destination_id: 3, source_id: 0 }]).expect("failed to set routes");
+    hub.set_lock(3, true).expect("failed to lock");
+    let start = Instant::now();
+    while hub.video_routes()[2] != 5 && start.elapsed() < Duration::from_secs(5) {
+        hub.poll(Duration::from_millis(50)).expect("failed to poll");
+    }
+
+    let entries = read_audit_log(&path).expect("failed to read audit log");
+    fs::remove_file(&path).expect("failed to remove audit log");
+    assert_eq!(entries.len(), 3);
+    assert!(entries.iter().all(|entry| entry.hub == "7C2E0D03192A"));
+
+    let local: Vec<_> = entries.iter().filter(|entry| entry.origin == AuditOrigin::Local).collect();
+    assert_eq!(local[0].change, AuditChange::Route { destination: 3, from: 3, to: 0 });
+    assert_eq!(
+        local[1].change,
+        AuditChange::Lock { destination: 3, from: LockState::Unlocked, to: LockState::Owned }
+    );
+    let external = entries.iter().find(|entry| entry.origin == AuditOrigin::External).expect("no external change");
+    assert_eq!(external.change, AuditChange::Route { destination: 2, from: 2, to: 5 });
+
+    let query = AuditQuery {
+        destinations: Some(IdSelection::Only(vec![3..=3])),
+        ..Default::default()
+    };
+    assert_eq!(query.filter(entries).expect("failed to query").len(), 2);
+}
+
+#[test]
+fn audit_log_does_record_changes_read_before_a_missing_ack() {
+    // a panel routes input 6 to output 3, then the router never acknowledges the command
+    let port = spawn_test_server(Some(|client: &mut TcpStream| {
+        read_to_newline(client, Some(Duration::from_secs(5))).unwrap_or_default();
+        client
+            .write_all("VIDEO OUTPUT ROUTING:\n2 5\n\n".as_bytes())
+            .expect("failed to send");
+    }));
+    let path = std::env::temp_dir().join(format!("hub_util_audit_{}.jsonl", port));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    hub.set_audit_log(Some(AuditLog::open(&path).expect("failed to open audit log")));
+
+    assert!(hub.set_routes(vec![VideoHubRoute { destination_id: 3, source_id: 0 }]).is_err());
+
+    let entries = read_audit_log(&path).expect("failed to read audit log");
+    fs::remove_file(&path).expect("failed to remove audit log");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].origin, AuditOrigin::External);
+    assert_eq!(entries[0].change, AuditChange::Route { destination: 2, from: 2, to: 5 });
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn audit_log_does_not_fail_changes_when_writing_fails() {
+    let port = spawn_test_server(Some(ack_all_commands));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    // every write to /dev/full fails with "no space left on device"
+    hub.set_audit_log(Some(AuditLog::open(Path::new("/dev/full")).expect("failed to open audit log")));
+
+    hub.set_routes(vec![VideoHubRoute { destination_id: 3, source_id: 0 }]).expect("failed to set routes");
+    assert_eq!(hub.video_routes()[3], 0);
+}
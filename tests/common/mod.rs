@@ -0,0 +1,213 @@
+#![allow(dead_code)]
+
+use hub_util::read_to_newline;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self};
+use std::time::Duration;
+
+pub fn spawn_test_server<F: FnOnce(&mut TcpStream) + Send + Copy + 'static>(func: Option<F>) -> i32 {
+    let random_port = rand::random_range(1024..9990);
+    let socket = TcpListener::bind(format!("127.0.0.1:{}", random_port)).expect("Could not start test TCP server");
+
+    thread::spawn(move || {
+        // loop {
+            let (mut client, _) = socket.accept().expect("Could not accept connection");
+            client.set_read_timeout(Some(Duration::from_millis(200))).expect("Failed to set Unit Test server read timeout");
+            client
+                .write_all(
+                    r#"PROTOCOL PREAMBLE:
+Version: 2.8
+
+VIDEOHUB DEVICE:
+Device present: true
+Model name: Blackmagic Smart Videohub 20 x 20
+Friendly name: Smart Videohub 20 x 20
+Unique ID: 7C2E0D03192A
+Video inputs: 20
+Video processing units: 0
+Video outputs: 20
+Video monitoring outputs: 0
+Serial ports: 0
+
+INPUT LABELS:
+0 Input 1
+1 Input 2
+2 Input 3
+3 Input 4
+4 Input 5
+5 Input 6
+6 Input 7
+7 Input 8
+8 Input 9
+9 Input 10
+10 Input 11
+11 Input 12
+12 Input 13
+13 Input 14
+14 Input 15
+15 Input 15
+16 Input 17
+17 Input 18
+18 Input 19
+19 Input 20
+
+OUTPUT LABELS:
+0 Output 1
+1 Output 2
+2 Output 3
+3 Output 4
+4 Output 5
+5 Output 6
+6 Output 7
+7 Output 8
+8 Output 9
+9 Output 10
+10 Output 11
+11 Output 12
+12 Output 13
+13 Output 14
+14 Output 15
+15 Output 16
+16 Output 17
+17 Output 18
+18 Output 19
+19 Output 20
+
+VIDEO OUTPUT LOCKS:
+0 U
+1 U
+2 U
+3 U
+4 U
+5 U
+6 U
+7 U
+8 U
+9 U
+10 U
+11 U
+12 U
+13 U
+14 U
+15 U
+16 U
+17 U
+18 U
+19 U
+
+VIDEO OUTPUT ROUTING:
+0 0
+1 1
+2 2
+3 3
+4 4
+5 5
+6 6
+7 7
+8 8
+9 9
+10 10
+11 11
+12 12
+13 13
+14 14
+15 15
+16 16
+17 17
+18 18
+19 19
+
+CONFIGURATION:
+Take Mode: true
+
+END PRELUDE:
+
+"#
+                        .as_bytes(),
+                )
+                .expect("Failed to write initial message to socket");
+
+            if let Some(ref server_func) = func {
+                server_func(&mut client);
+                println!("server func ran");
+            }
+
+            // wait for client to close socket
+            let _ = client.read_to_end(&mut vec![]);
+        // }
+    });
+    random_port
+}
+
+pub const EMPTY_FUNC: Option<fn(&mut TcpStream)> = None::<fn(&mut TcpStream)>;
+
+// Labels and routes of the test server, starting as sent in the prelude
+pub struct TestRouter {
+    pub input_labels: Vec<String>,
+    pub output_labels: Vec<String>,
+    pub routes: Vec<usize>,
+}
+
+impl Default for TestRouter {
+    fn default() -> Self {
+        let mut input_labels: Vec<String> = (1..=20).map(|i| format!("Input {}", i)).collect();
+        input_labels[15] = "Input 15".to_string();
+        TestRouter {
+            input_labels,
+            output_labels: (1..=20).map(|i| format!("Output {}", i)).collect(),
+            routes: (0..20).collect(),
+        }
+    }
+}
+
+impl TestRouter {
+    // Applies the labels and routes of a command
+    pub fn apply(&mut self, cmd: &str) {
+        let mut lines = cmd.lines();
+        let header = lines.next().unwrap_or_default();
+        for line in lines.filter(|line| !line.is_empty()) {
+            let (id, value) = line.split_once(' ').expect("invalid line");
+            let id: usize = id.parse().expect("invalid id");
+            match header {
+                "INPUT LABELS:" => self.input_labels[id] = value.to_string(),
+                "OUTPUT LABELS:" => self.output_labels[id] = value.to_string(),
+                "VIDEO OUTPUT ROUTING:" => self.routes[id] = value.parse().expect("invalid source"),
+                _ => continue,
+            }
+        }
+    }
+    // The whole section if the command is a query, a block without lines
+    pub fn answer(&self, cmd: &str) -> Option<String> {
+        let lines = match cmd.trim_end() {
+            "INPUT LABELS:" => self.input_labels.clone(),
+            "OUTPUT LABELS:" => self.output_labels.clone(),
+            "VIDEO OUTPUT ROUTING:" => self.routes.iter().map(|source| source.to_string()).collect(),
+            _ => return None,
+        };
+        let mut block = cmd.trim_end().to_string() + "\n";
+        for (id, line) in lines.iter().enumerate() {
+            block += &format!("{} {}\n", id, line);
+        }
+        Some(block + "\n")
+    }
+}
+
+// Acknowledges every command and echoes it back like a Videohub does
+pub fn ack_all_commands(client: &mut TcpStream) {
+    let mut router = TestRouter::default();
+    loop {
+        let cmd = read_to_newline(client, Some(Duration::from_secs(5))).unwrap_or_default();
+        if cmd.is_empty() {
+            return;
+        }
+        client.write_all("ACK\n\n".as_bytes()).expect("failed to send");
+        match router.answer(&cmd) {
+            Some(answer) => client.write_all(answer.as_bytes()).expect("failed to send"),
+            None => {
+                router.apply(&cmd);
+                client.write_all(cmd.as_bytes()).expect("failed to send");
+            }
+        }
+    }
+}
@@ -1,148 +1,16 @@
 ﻿extern crate hub_util;
 
-use hub_util::video_hub::{VideoHub, VideoHubLabelType};
+mod common;
+
+use common::{ack_all_commands, spawn_test_server, TestRouter, EMPTY_FUNC};
+use hub_util::video_hub::{IdSelection, ImportFilter, ImportMismatch, ImportRollback, VideoHub, VideoHubDump, VideoHubLabel, VideoHubLabelType, VideoHubRoute};
 use hub_util::read_to_newline;
+use regex::Regex;
 use serde_json::Value;
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
-use std::thread::{self};
-use std::time::Duration;
-
-fn spawn_test_server<F: FnOnce(&mut TcpStream) -> () + Send + Copy + 'static>(func: Option<F>) -> i32 {
-    let random_port = rand::random_range(1024..9990);
-    let socket = TcpListener::bind(format!("127.0.0.1:{}", random_port)).expect("Could not start test TCP server");
-
-    thread::spawn(move || {
-        // loop {
-            let (mut client, _) = socket.accept().expect("Could not accept connection");
-            client.set_read_timeout(Some(Duration::from_millis(200))).expect("Failed to set Unit Test server read timeout");
-            client
-                .write(
-                    r#"PROTOCOL PREAMBLE:
-Version: 2.8
-
-VIDEOHUB DEVICE:
-Device present: true
-Model name: Blackmagic Smart Videohub 20 x 20
-Friendly name: Smart Videohub 20 x 20
-Unique ID: 7C2E0D03192A
-Video inputs: 20
-Video processing units: 0
-Video outputs: 20
-Video monitoring outputs: 0
-Serial ports: 0
-
-INPUT LABELS:
-0 Input 1
-1 Input 2
-2 Input 3
-3 Input 4
-4 Input 5
-5 Input 6
-6 Input 7
-7 Input 8
-8 Input 9
-9 Input 10
-10 Input 11
-11 Input 12
-12 Input 13
-13 Input 14
-14 Input 15
-15 Input 15
-16 Input 17
-17 Input 18
-18 Input 19
-19 Input 20
-
-OUTPUT LABELS:
-0 Output 1
-1 Output 2
-2 Output 3
-3 Output 4
-4 Output 5
-5 Output 6
-6 Output 7
-7 Output 8
-8 Output 9
-9 Output 10
-10 Output 11
-11 Output 12
-12 Output 13
-13 Output 14
-14 Output 15
-15 Output 16
-16 Output 17
-17 Output 18
-18 Output 19
-19 Output 20
-
-VIDEO OUTPUT LOCKS:
-0 U
-1 U
-2 U
-3 U
-4 U
-5 U
-6 U
-7 U
-8 U
-9 U
-10 U
-11 U
-12 U
-13 U
-14 U
-15 U
-16 U
-17 U
-18 U
-19 U
-
-VIDEO OUTPUT ROUTING:
-0 0
-1 1
-2 2
-3 3
-4 4
-5 5
-6 6
-7 7
-8 8
-9 9
-10 10
-11 11
-12 12
-13 13
-14 14
-15 15
-16 16
-17 17
-18 18
-19 19
-
-CONFIGURATION:
-Take Mode: true
-
-END PRELUDE:
-
-"#
-                        .as_bytes(),
-                )
-                .expect("Failed to write initial message to socket");
-
-            if let Some(ref server_func) = func {
-                server_func(&mut client);
-                println!("server func ran");
-            }
-
-            // wait for client to close socket
-            let _ = client.read_to_end(&mut vec![]);
-        // }
-    });
-    random_port
-}
-
-const EMPTY_FUNC: Option<fn(&mut TcpStream)> = None::<fn(&mut TcpStream) -> ()>;
+use std::io::Write;
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[test]
 fn videohub_does_parse_hello_message() {
@@ -185,7 +53,30 @@ fn videohub_does_not_import_broken_json() {
 }"#;
 
     let result = hub.import_dump(json);
-    assert_eq!(result.is_ok(), false);
+    assert!(result.is_err());
+}
+
+#[test]
+fn videohub_does_not_import_out_of_range_ids() {
+    let port = spawn_test_server(Some(ack_all_commands));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let json = r#"{
+    "formatVersion": 3,
+    "time": 1742323854265,
+    "idBase": 1,
+    "name": "Blackmagic Smart Videohub 20 x 20",
+    "sources": [{"id": 1, "name": "Camera 1"}],
+    "destinations": [],
+    "routes": [{"destinationId": 1, "sourceId": 21}]
+}"#;
+
+    let error = hub.import_dump(json).expect_err("import should fail");
+    assert_eq!(error.to_string(), "Dump contains input 21 but VideoHub contains 20 inputs");
+    assert_eq!(hub.command_stats().sent, 0);
+    assert_eq!(hub.input_labels()[0], "Input 1");
 }
 
 #[test]
@@ -196,11 +87,11 @@ fn videohub_does_import_json() {
             let cmd = read_to_newline(client, None).unwrap_or_default();
             assert_ne!(cmd.len(), 0);
             println!("serv: client command: {:?}", cmd);
-            client.write("ACK\n\n".as_bytes()).expect("failed to send");
+            client.write_all("ACK\n\n".as_bytes()).expect("failed to send");
             println!("serv: wrote ack");
             if cmd.contains("LABELS") || cmd.contains("ROUTING") {
                 // server will send back changes for clients to update
-                client.write(cmd.as_bytes()).expect("failed to send");
+                client.write_all(cmd.as_bytes()).expect("failed to send");
             }
         }
     }));
@@ -212,11 +103,27 @@ fn videohub_does_import_json() {
 
     let result = hub.import_dump(json);
 
-    assert_eq!(result.is_ok(), true);
+    assert!(result.is_ok());
     assert_eq!(hub.input_labels()[0], "Src 1");
     assert_eq!(hub.output_labels()[0], "Dest 1");
 }
 
+#[test]
+fn videohub_does_not_apply_rejected_command() {
+    let port = spawn_test_server(Some(|client: &mut TcpStream| {
+        let cmd = read_to_newline(client, None).unwrap_or_default();
+        assert_ne!(cmd.len(), 0);
+        client.write_all("NACK\n\n".as_bytes()).expect("failed to send");
+    }));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let result = hub.set_label(VideoHubLabelType::Input, 0, "Src 1");
+    assert!(result.is_err());
+    assert_eq!(hub.input_labels()[0], "Input 1");
+}
+
 #[test]
 fn videohub_does_send_command() {
     let port = spawn_test_server(Some(|client: &mut TcpStream| {
@@ -224,7 +131,7 @@ fn videohub_does_send_command() {
         let cmd = read_to_newline(client, None).unwrap_or_default();
         assert_ne!(cmd.len(), 0);
         println!("serv: client command: {:?}", cmd);
-        client.write("ACK\n\n".as_bytes()).expect("failed to send");
+        client.write_all("ACK\n\n".as_bytes()).expect("failed to send");
         println!("serv: wrote ack");
     }));
 
@@ -232,4 +139,383 @@ fn videohub_does_send_command() {
         .expect("failed to parse videohub");
 
     hub.set_label(VideoHubLabelType::Input, 0, "test label").expect("Failed to set label");
-}
\ No newline at end of file
+}
+
+#[test]
+fn videohub_does_import_one_based_json() {
+    let port = spawn_test_server(Some(ack_all_commands));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let json = r#"{
+    "formatVersion": 3,
+    "time": 1742323854265,
+    "idBase": 1,
+    "name": "Blackmagic Smart Videohub 20 x 20",
+    "sources": [{"id": 1, "name": "Src 1"}, {"id": 2, "name": "Src 2"}],
+    "destinations": [{"id": 1, "name": "Dest 1"}],
+    "routes": [{"destinationId": 1, "sourceId": 2}]
+}"#;
+
+    hub.import_dump(json).expect("failed to import dump");
+    assert_eq!(hub.input_labels()[0], "Src 1");
+    assert_eq!(hub.input_labels()[1], "Src 2");
+    assert_eq!(hub.output_labels()[0], "Dest 1");
+    assert_eq!(hub.video_routes()[0], 1);
+}
+
+#[test]
+fn videohub_does_import_selected_routes() {
+    let port = spawn_test_server(Some(ack_all_commands));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let json = r#"{
+    "formatVersion": 3,
+    "time": 1742323854265,
+    "idBase": 0,
+    "name": "Blackmagic Smart Videohub 20 x 20",
+    "sources": [{"id": 0, "name": "Src 1"}],
+    "destinations": [{"id": 0, "name": "Dest 1"}],
+    "routes": [{"destinationId": 0, "sourceId": 5}, {"destinationId": 1, "sourceId": 6}, {"destinationId": 2, "sourceId": 7}]
+}"#;
+    let filter = ImportFilter {
+        sources: IdSelection::None,
+        destinations: IdSelection::None,
+        routes: "1-2".parse().expect("failed to parse selection"),
+    };
+
+    hub.import_filtered(VideoHubDump::from_json(json).expect("failed to parse dump"), &filter)
+        .expect("failed to import dump");
+    assert_eq!(hub.input_labels()[0], "Input 1");
+    assert_eq!(hub.output_labels()[0], "Output 1");
+    assert_eq!(&hub.video_routes()[0..3], &[0, 6, 7]);
+}
+
+#[test]
+fn videohub_does_diff_dump_without_sending() {
+    // another client locks output 3, the server does not answer any commands
+    let port = spawn_test_server(Some(|client: &mut TcpStream| {
+        client
+            .write_all("VIDEO OUTPUT LOCKS:\n2 L\n\n".as_bytes())
+            .expect("failed to send");
+        thread::sleep(Duration::from_millis(500));
+    }));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    hub.poll(Duration::from_millis(200)).expect("failed to poll");
+
+    let json = r#"{
+    "formatVersion": 3,
+    "time": 1742323854265,
+    "idBase": 0,
+    "name": "Blackmagic Smart Videohub 20 x 20",
+    "sources": [{"id": 0, "name": "Camera 1"}, {"id": 1, "name": "Input 2"}],
+    "destinations": [],
+    "routes": [{"destinationId": 1, "sourceId": 1}, {"destinationId": 2, "sourceId": 0}]
+}"#;
+
+    let diff = hub
+        .diff(VideoHubDump::from_json(json).expect("failed to parse dump"), &ImportFilter::default())
+        .expect("failed to diff dump");
+    assert_eq!(diff.sources.len(), 1);
+    assert_eq!(diff.sources[0].to, "Camera 1");
+    assert_eq!(diff.routes.len(), 1);
+    assert_eq!(diff.routes[0].destination_id, 2);
+    assert_eq!(diff.locked, vec![2]);
+    assert_eq!(hub.input_labels()[0], "Input 1");
+}
+
+#[test]
+fn videohub_does_roll_back_failed_import() {
+    // refuses the routing block and accepts everything else
+    let port = spawn_test_server(Some(|client: &mut TcpStream| loop {
+        let cmd = read_to_newline(client, Some(Duration::from_secs(5))).unwrap_or_default();
+        if cmd.is_empty() {
+            return;
+        }
+        if cmd.starts_with("VIDEO OUTPUT ROUTING:") && cmd.contains("0 5") {
+            client.write_all("NACK\n\n".as_bytes()).expect("failed to send");
+            continue;
+        }
+        client.write_all("ACK\n\n".as_bytes()).expect("failed to send");
+        client.write_all(cmd.as_bytes()).expect("failed to send");
+    }));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let json = r#"{
+    "formatVersion": 3,
+    "time": 1742323854265,
+    "idBase": 0,
+    "name": "Blackmagic Smart Videohub 20 x 20",
+    "sources": [{"id": 0, "name": "Camera 1"}],
+    "destinations": [],
+    "routes": [{"destinationId": 0, "sourceId": 5}]
+}"#;
+
+    let error = hub.import_dump(json).expect_err("import should fail");
+    let rollback = error.downcast_ref::<ImportRollback>().expect("import was not rolled back");
+    assert!(rollback.rollback_error.is_none());
+    assert_eq!(rollback.rolled_back.sources[0].to, "Input 1");
+    assert_eq!(rollback.rolled_back.routes[0].to, 0);
+    assert_eq!(hub.input_labels()[0], "Input 1");
+    assert_eq!(hub.video_routes()[0], 0);
+}
+
+#[test]
+fn videohub_does_verify_echoed_state_after_import() {
+    // output 1 is locked by a panel, the router acknowledges the route but echoes the old one
+    let port = spawn_test_server(Some(|client: &mut TcpStream| loop {
+        let cmd = read_to_newline(client, Some(Duration::from_secs(5))).unwrap_or_default();
+        if cmd.is_empty() {
+            return;
+        }
+        client.write_all("ACK\n\n".as_bytes()).expect("failed to send");
+        if cmd.starts_with("VIDEO OUTPUT ROUTING:") {
+            client
+                .write_all("VIDEO OUTPUT ROUTING:\n0 0\n\n".as_bytes())
+                .expect("failed to send");
+        } else {
+            client.write_all(cmd.as_bytes()).expect("failed to send");
+        }
+    }));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let json = r#"{
+    "formatVersion": 3,
+    "time": 1742323854265,
+    "idBase": 0,
+    "name": "Blackmagic Smart Videohub 20 x 20",
+    "sources": [{"id": 0, "name": "Camera 1"}],
+    "destinations": [],
+    "routes": [{"destinationId": 0, "sourceId": 5}, {"destinationId": 1, "sourceId": 1}]
+}"#;
+
+    let error = hub.import_dump(json).expect_err("import should fail");
+    let rollback = error.downcast_ref::<ImportRollback>().expect("import was not rolled back");
+    let mismatch = rollback.cause.downcast_ref::<ImportMismatch>().expect("cause is not a mismatch");
+    assert_eq!(
+        mismatch,
+        &ImportMismatch {
+            input_labels: vec![],
+            output_labels: vec![],
+            routes: vec![0],
+        }
+    );
+    assert_eq!(hub.input_labels()[0], "Input 1");
+}
+
+#[test]
+fn videohub_does_roll_back_import_the_router_did_not_apply() {
+    // acknowledges every change without applying or echoing it
+    let port = spawn_test_server(Some(|client: &mut TcpStream| {
+        let router = TestRouter::default();
+        loop {
+            let cmd = read_to_newline(client, Some(Duration::from_secs(5))).unwrap_or_default();
+            if cmd.is_empty() {
+                return;
+            }
+            client.write_all("ACK\n\n".as_bytes()).expect("failed to send");
+            if let Some(answer) = router.answer(&cmd) {
+                client.write_all(answer.as_bytes()).expect("failed to send");
+            }
+        }
+    }));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let json = r#"{
+    "formatVersion": 3,
+    "time": 1742323854265,
+    "idBase": 0,
+    "name": "Blackmagic Smart Videohub 20 x 20",
+    "sources": [{"id": 0, "name": "Camera 1"}],
+    "destinations": [],
+    "routes": [{"destinationId": 0, "sourceId": 5}]
+}"#;
+
+    let error = hub.import_dump(json).expect_err("import should fail");
+    let rollback = error.downcast_ref::<ImportRollback>().expect("import was not rolled back");
+    let mismatch = rollback.cause.downcast_ref::<ImportMismatch>().expect("cause is not a mismatch");
+    assert_eq!(
+        mismatch,
+        &ImportMismatch {
+            input_labels: vec![0],
+            output_labels: vec![],
+            routes: vec![0],
+        }
+    );
+    assert_eq!(hub.input_labels()[0], "Input 1");
+    assert_eq!(hub.video_routes()[0], 0);
+}
+
+#[test]
+fn videohub_does_route_by_number_range_and_label() {
+    let port = spawn_test_server(Some(ack_all_commands));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let specs: Vec<String> = ["1-3=5", "5-6=7-8", "output 10=Input 2"].iter().map(|spec| spec.to_string()).collect();
+    let routes = hub.parse_routes(&specs, 1).expect("failed to parse routes");
+    assert_eq!(routes.len(), 6);
+    hub.set_routes(routes).expect("failed to set routes");
+
+    assert_eq!(&hub.video_routes()[0..6], &[4, 4, 4, 3, 6, 7]);
+    assert_eq!(hub.video_routes()[9], 1);
+
+    let specs = vec!["1-3=1-2".to_string()];
+    assert!(hub.parse_routes(&specs, 1).is_err());
+}
+
+#[test]
+fn videohub_does_set_labels_from_lines_and_regex() {
+    let port = spawn_test_server(Some(ack_all_commands));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let labels = hub
+        .parse_label_lines(VideoHubLabelType::Input, "# cameras\n1 Camera 1\n\n2 Camera 2\n", 1)
+        .expect("failed to parse labels");
+    hub.set_labels(VideoHubLabelType::Input, labels).expect("failed to set labels");
+    assert_eq!(&hub.input_labels()[0..3], &["Camera 1", "Camera 2", "Input 3"]);
+    assert!(hub.parse_label_lines(VideoHubLabelType::Input, "21 Camera 21", 1).is_err());
+
+    let pattern = Regex::new(r"^Output (\d+)$").expect("failed to parse regex");
+    let labels = hub.replace_labels(VideoHubLabelType::Output, &pattern, "Monitor $1");
+    assert_eq!(labels.len(), 20);
+    hub.set_labels(VideoHubLabelType::Output, labels).expect("failed to set labels");
+    assert_eq!(hub.output_labels()[19], "Monitor 20");
+}
+
+#[test]
+fn videohub_does_take_pending_routes() {
+    let port = spawn_test_server(Some(ack_all_commands));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    hub.stage_route(2, 5).expect("failed to stage route");
+    hub.stage_route(2, 6).expect("failed to stage route");
+    hub.stage_route(3, 3).expect("failed to stage route");
+    assert!(hub.stage_route(20, 0).is_err());
+    assert_eq!(hub.pending_routes().len(), 2);
+    assert_eq!(hub.video_routes()[2], 2);
+
+    let diff = hub.pending_diff();
+    assert_eq!(diff.routes.len(), 1);
+    assert_eq!((diff.routes[0].from, diff.routes[0].to), (2, 6));
+
+    hub.cancel();
+    assert!(hub.pending_routes().is_empty());
+    assert!(hub.take().expect("failed to take").is_empty());
+
+    hub.stage_route(2, 6).expect("failed to stage route");
+    let diff = hub.take().expect("failed to take");
+    assert_eq!(diff.routes.len(), 1);
+    assert_eq!(hub.video_routes()[2], 6);
+    assert!(hub.pending_routes().is_empty());
+}
+
+#[test]
+fn videohub_does_undo_and_redo_changes() {
+    let port = spawn_test_server(Some(ack_all_commands));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    assert!(hub.undo().expect("failed to undo").is_none());
+
+    hub.set_routes(vec![VideoHubRoute { destination_id: 2, source_id: 5 }, VideoHubRoute { destination_id: 3, source_id: 3 }])
+        .expect("failed to set routes");
+    hub.set_labels(VideoHubLabelType::Output, vec![VideoHubLabel { id: 0, name: "Program".to_string() }])
+        .expect("failed to set labels");
+    assert_eq!(hub.history().len(), 2);
+    assert_eq!(hub.history()[0].diff.routes.len(), 1);
+
+    let entry = hub.undo().expect("failed to undo").expect("nothing undone");
+    assert_eq!(entry.diff.to_string(), "Output label 0: \"Output 1\" -> \"Program\"\n");
+    assert_eq!(hub.output_labels()[0], "Output 1");
+    hub.undo().expect("failed to undo");
+    assert_eq!(hub.video_routes()[2], 2);
+    assert!(hub.history().is_empty());
+    assert_eq!(hub.redo_history().len(), 2);
+
+    hub.redo().expect("failed to redo");
+    assert_eq!(hub.video_routes()[2], 5);
+    hub.set_routes(vec![VideoHubRoute { destination_id: 4, source_id: 0 }]).expect("failed to set routes");
+    assert!(hub.redo_history().is_empty());
+    assert!(hub.redo().expect("failed to redo").is_none());
+}
+
+#[test]
+fn videohub_does_undo_external_changes() {
+    let port = spawn_test_server(Some(|client: &mut TcpStream| {
+        thread::sleep(Duration::from_millis(100));
+        client
+            .write_all("VIDEO OUTPUT ROUTING:\n2 5\n\n".as_bytes())
+            .expect("failed to send");
+        ack_all_commands(client);
+    }));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    hub.record_external_changes(true);
+
+    let start = Instant::now();
+    while hub.history().is_empty() && start.elapsed() < Duration::from_secs(5) {
+        hub.poll(Duration::from_millis(50)).expect("failed to poll");
+    }
+    assert!(hub.history()[0].external);
+
+    hub.undo().expect("failed to undo");
+    assert_eq!(hub.video_routes()[2], 2);
+}
+
+#[test]
+fn videohub_does_drop_redo_after_external_change() {
+    // a panel routes output 3 shortly after the second command
+    let port = spawn_test_server(Some(|client: &mut TcpStream| {
+        let mut commands = 0;
+        loop {
+            let cmd = read_to_newline(client, Some(Duration::from_secs(5))).unwrap_or_default();
+            if cmd.is_empty() {
+                return;
+            }
+            client.write_all("ACK\n\n".as_bytes()).expect("failed to send");
+            client.write_all(cmd.as_bytes()).expect("failed to send");
+            commands += 1;
+            if commands == 2 {
+                thread::sleep(Duration::from_millis(100));
+                client
+                    .write_all("VIDEO OUTPUT ROUTING:\n2 5\n\n".as_bytes())
+                    .expect("failed to send");
+            }
+        }
+    }));
+
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    hub.record_external_changes(true);
+
+    hub.set_routes(vec![VideoHubRoute { destination_id: 3, source_id: 7 }])
+        .expect("failed to set route");
+    hub.undo().expect("failed to undo");
+    assert_eq!(hub.redo_history().len(), 1);
+
+    let start = Instant::now();
+    while hub.history().is_empty() && start.elapsed() < Duration::from_secs(5) {
+        hub.poll(Duration::from_millis(50)).expect("failed to poll");
+    }
+    assert!(hub.history()[0].external);
+    assert!(hub.redo_history().is_empty());
+    assert!(hub.redo().expect("failed to redo").is_none());
+}
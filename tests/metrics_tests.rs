@@ -0,0 +1,126 @@
+extern crate hub_util;
+
+mod common;
+
+use common::{spawn_test_server, EMPTY_FUNC};
+use hub_util::audit::{read_audit_log, AuditChange};
+use hub_util::metrics::{MetricsExporter, REQUEST_TIMEOUT};
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn scrape(exporter: &mut MetricsExporter, addr: &str) -> String {
+    let mut client = TcpStream::connect(addr).expect("failed to connect to exporter");
+    client
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .expect("failed to send request");
+    exporter.step(Duration::from_millis(50)).expect("exporter step failed");
+
+    let mut response = String::new();
+    client.read_to_string(&mut response).expect("failed to read response");
+    response
+}
+
+#[test]
+fn metrics_exporter_does_serve_routes() {
+    let port = spawn_test_server(EMPTY_FUNC);
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind metrics listener");
+    let addr = listener.local_addr().expect("failed to get listener address").to_string();
+
+    let mut exporter = MetricsExporter::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"), listener)
+        .expect("failed to create exporter");
+    exporter.step(Duration::from_millis(50)).expect("exporter step failed");
+    assert!(exporter.is_connected());
+
+    let response = scrape(&mut exporter, &addr);
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("\nvideohub_up 1\n"));
+    assert!(response.contains(
+        "videohub_route_info{destination=\"2\",destination_label=\"Output 3\",source=\"2\",source_label=\"Input 3\"} 1"
+    ));
+    assert!(response.contains("videohub_output_locked{destination=\"0\"} 0"));
+}
+
+#[test]
+fn metrics_exporter_does_report_lost_connection() {
+    let port = spawn_test_server(Some(|client: &mut TcpStream| {
+        thread::sleep(Duration::from_millis(100));
+        client.shutdown(Shutdown::Both).expect("failed to close connection");
+    }));
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind metrics listener");
+    let addr = listener.local_addr().expect("failed to get listener address").to_string();
+
+    let mut exporter = MetricsExporter::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"), listener)
+        .expect("failed to create exporter");
+    exporter.step(Duration::from_millis(50)).expect("exporter step failed");
+    assert!(exporter.is_connected());
+
+    let start = Instant::now();
+    while exporter.is_connected() && start.elapsed() < Duration::from_secs(5) {
+        exporter.step(Duration::from_millis(50)).expect("exporter step failed");
+    }
+
+    let response = scrape(&mut exporter, &addr);
+    assert!(response.contains("\nvideohub_up 0\n"));
+    assert!(!response.contains("videohub_route_info{"));
+}
+
+#[test]
+fn metrics_exporter_does_not_wait_for_slow_scrapers() {
+    let port = spawn_test_server(EMPTY_FUNC);
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind metrics listener");
+    let addr = listener.local_addr().expect("failed to get listener address").to_string();
+
+    let mut exporter = MetricsExporter::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"), listener)
+        .expect("failed to create exporter");
+    // a scraper that never finishes its request
+    let mut slow = TcpStream::connect(&addr).expect("failed to connect to exporter");
+    slow.write_all(b"GET /metrics HTTP/1.1\r\n").expect("failed to send request");
+
+    let start = Instant::now();
+    exporter.step(Duration::from_millis(50)).expect("exporter step failed");
+    assert!(start.elapsed() < REQUEST_TIMEOUT);
+    assert!(exporter.is_connected());
+    let response = scrape(&mut exporter, &addr);
+    assert!(response.contains("\nvideohub_up 1\n"));
+    assert!(start.elapsed() < REQUEST_TIMEOUT);
+
+    // the slow scraper is disconnected once its time is up
+    while start.elapsed() <= REQUEST_TIMEOUT {
+        exporter.step(Duration::from_millis(50)).expect("exporter step failed");
+    }
+    exporter.step(Duration::from_millis(50)).expect("exporter step failed");
+    slow.set_read_timeout(Some(Duration::from_secs(1))).expect("failed to set timeout");
+    let mut response = Vec::new();
+    slow.read_to_end(&mut response).expect("failed to read response");
+    assert!(response.is_empty());
+}
+
+#[test]
+fn metrics_exporter_does_write_audit_log() {
+    // a panel routes input 6 to output 3 while the exporter is connected
+    let port = spawn_test_server(Some(|client: &mut TcpStream| {
+        thread::sleep(Duration::from_millis(100));
+        client
+            .write_all("VIDEO OUTPUT ROUTING:\n2 5\n\n".as_bytes())
+            .expect("failed to send");
+    }));
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind metrics listener");
+    let path = std::env::temp_dir().join(format!("hub_util_metrics_audit_{}.jsonl", port));
+
+    let mut exporter = MetricsExporter::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"), listener)
+        .expect("failed to create exporter");
+    exporter.audit_log = Some(path.clone());
+    let start = Instant::now();
+    while !path.exists() || read_audit_log(&path).expect("failed to read audit log").is_empty() {
+        assert!(start.elapsed() < Duration::from_secs(5), "change was not logged");
+        exporter.step(Duration::from_millis(50)).expect("exporter step failed");
+    }
+
+    let entries = read_audit_log(&path).expect("failed to read audit log");
+    fs::remove_file(&path).expect("failed to remove audit log");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].change, AuditChange::Route { destination: 2, from: 2, to: 5 });
+}
@@ -0,0 +1,154 @@
+extern crate hub_util;
+
+mod common;
+
+use common::{ack_all_commands, spawn_test_server};
+use hub_util::mqtt::{MqttBridge, MqttClient};
+use hub_util::video_hub::VideoHub;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn read_packet(client: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+    let mut header = [0u8; 1];
+    client.read_exact(&mut header).ok()?;
+    let mut len = 0usize;
+    let mut multiplier = 1usize;
+    loop {
+        let mut byte = [0u8; 1];
+        client.read_exact(&mut byte).ok()?;
+        len += (byte[0] & 0x7F) as usize * multiplier;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+        multiplier *= 128;
+    }
+    let mut body = vec![0u8; len];
+    client.read_exact(&mut body).ok()?;
+    Some((header[0], body))
+}
+
+fn publish_packet(topic: &str, payload: &str) -> Vec<u8> {
+    let mut body = (topic.len() as u16).to_be_bytes().to_vec();
+    body.extend_from_slice(topic.as_bytes());
+    body.extend_from_slice(payload.as_bytes());
+    let mut packet = vec![0x30, body.len() as u8];
+    packet.extend(body);
+    packet
+}
+
+// Stand-in for a broker: accepts one client, sends `command` once the client
+// subscribes and forwards every PUBLISH from the client as (topic, payload, retain)
+fn spawn_test_broker(command: (&'static str, &'static str)) -> (u16, Receiver<(String, String, bool)>) {
+    let socket = TcpListener::bind("127.0.0.1:0").expect("Could not start test broker");
+    let port = socket.local_addr().expect("Failed to get broker address").port();
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let (mut client, _) = socket.accept().expect("Could not accept connection");
+        while let Some((header, body)) = read_packet(&mut client) {
+            match header & 0xF0 {
+                0x10 => client.write_all(&[0x20, 0x02, 0x00, 0x00]).expect("failed to send"),
+                0x30 => {
+                    let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                    let topic = String::from_utf8(body[2..2 + topic_len].to_vec()).expect("invalid topic");
+                    let payload = String::from_utf8(body[2 + topic_len..].to_vec()).expect("invalid payload");
+                    let _ = sender.send((topic, payload, header & 0x01 != 0));
+                }
+                0x80 => {
+                    client.write_all(&[0x90, 0x03, body[0], body[1], 0x00]).expect("failed to send");
+                    client.write_all(&publish_packet(command.0, command.1)).expect("failed to send");
+                }
+                _ => continue,
+            }
+        }
+    });
+    (port, receiver)
+}
+
+#[test]
+fn mqtt_bridge_does_publish_state() {
+    let port = spawn_test_server(Some(ack_all_commands));
+    let (broker_port, published) = spawn_test_broker(("unused", ""));
+
+    let hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    let client = MqttClient::connect(format!("127.0.0.1:{}", broker_port).parse().expect("Failed to parse broker IP"), "test")
+        .expect("failed to connect to broker");
+
+    let mut bridge = MqttBridge::new(hub, client, "videohub");
+    bridge.start().expect("failed to start bridge");
+
+    let messages: Vec<(String, String, bool)> = published.iter().take(61).collect();
+    assert!(messages.iter().all(|(_, _, retain)| *retain));
+    assert!(messages.contains(&("videohub/7C2E0D03192A/route/4".to_string(), "4".to_string(), true)));
+    assert!(messages.contains(&("videohub/7C2E0D03192A/label/input/0".to_string(), "Input 1".to_string(), true)));
+    assert!(messages.contains(&("videohub/7C2E0D03192A/label/output/19".to_string(), "Output 20".to_string(), true)));
+}
+
+#[test]
+fn mqtt_bridge_does_apply_route_command() {
+    let port = spawn_test_server(Some(ack_all_commands));
+    let (broker_port, published) = spawn_test_broker(("videohub/7C2E0D03192A/route/3/set", "7"));
+
+    let hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    let client = MqttClient::connect(format!("127.0.0.1:{}", broker_port).parse().expect("Failed to parse broker IP"), "test")
+        .expect("failed to connect to broker");
+
+    let mut bridge = MqttBridge::new(hub, client, "videohub");
+    bridge.start().expect("failed to start bridge");
+
+    let start = Instant::now();
+    while bridge.hub().video_routes()[3] != 7 && start.elapsed() < Duration::from_secs(5) {
+        bridge.step(Duration::from_millis(50)).expect("bridge step failed");
+    }
+    assert_eq!(bridge.hub().video_routes()[3], 7);
+
+    let route_update = published
+        .iter()
+        .find(|(topic, payload, _)| topic == "videohub/7C2E0D03192A/route/3" && payload == "7");
+    assert!(route_update.is_some());
+}
+
+#[test]
+fn mqtt_bridge_does_not_send_out_of_range_command() {
+    let port = spawn_test_server(Some(ack_all_commands));
+    let (broker_port, _published) = spawn_test_broker(("videohub/7C2E0D03192A/route/3/set", "20"));
+
+    let hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    let client = MqttClient::connect(format!("127.0.0.1:{}", broker_port).parse().expect("Failed to parse broker IP"), "test")
+        .expect("failed to connect to broker");
+
+    let mut bridge = MqttBridge::new(hub, client, "videohub");
+    bridge.start().expect("failed to start bridge");
+    for _ in 0..10 {
+        bridge.step(Duration::from_millis(50)).expect("bridge step failed");
+    }
+    assert_eq!(bridge.hub().command_stats().sent, 0);
+    assert_eq!(bridge.hub().video_routes()[3], 3);
+}
+
+#[test]
+fn mqtt_bridge_does_not_send_label_with_line_break() {
+    let port = spawn_test_server(Some(ack_all_commands));
+    let (broker_port, _published) =
+        spawn_test_broker(("videohub/7C2E0D03192A/label/input/0/set", "Cam\n\nVIDEO OUTPUT ROUTING:\n0 5"));
+
+    let hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    let client = MqttClient::connect(format!("127.0.0.1:{}", broker_port).parse().expect("Failed to parse broker IP"), "test")
+        .expect("failed to connect to broker");
+
+    let mut bridge = MqttBridge::new(hub, client, "videohub");
+    bridge.start().expect("failed to start bridge");
+    for _ in 0..10 {
+        bridge.step(Duration::from_millis(50)).expect("bridge step failed");
+    }
+    assert_eq!(bridge.hub().command_stats().sent, 0);
+    assert_eq!(bridge.hub().input_labels()[0], "Input 1");
+    assert_eq!(bridge.hub().video_routes()[0], 0);
+}
@@ -0,0 +1,75 @@
+extern crate hub_util;
+
+mod common;
+
+use common::{ack_all_commands, spawn_test_server};
+use hub_util::osc::{OscArg, OscBridge, OscMessage};
+use hub_util::video_hub::VideoHub;
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+#[test]
+fn osc_bridge_does_route_and_send_feedback() {
+    let port = spawn_test_server(Some(ack_all_commands));
+
+    let hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let socket = UdpSocket::bind("127.0.0.1:0").expect("failed to bind bridge socket");
+    let bridge_addr = socket.local_addr().expect("failed to get bridge address");
+    let surface = UdpSocket::bind("127.0.0.1:0").expect("failed to bind surface socket");
+    surface
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("failed to set read timeout");
+
+    let mut bridge = OscBridge::new(hub, socket, vec![surface.local_addr().expect("failed to get surface address")]);
+
+    // TouchOSC sends numbers as floats
+    let msg = OscMessage::new("/videohub/route", vec![OscArg::Float(3.0), OscArg::Float(5.0)]);
+    surface.send_to(&msg.encode(), bridge_addr).expect("failed to send");
+
+    let start = Instant::now();
+    while bridge.hub().video_routes()[3] != 5 && start.elapsed() < Duration::from_secs(5) {
+        bridge.step(Duration::from_millis(50)).expect("bridge step failed");
+    }
+    assert_eq!(bridge.hub().video_routes()[3], 5);
+
+    let mut buf = [0u8; 1024];
+    let (size, _) = surface.recv_from(&mut buf).expect("no feedback received");
+    let feedback = OscMessage::decode(&buf[..size]).expect("failed to decode feedback");
+    assert_eq!(
+        feedback,
+        vec![OscMessage::new("/videohub/route", vec![OscArg::Int(3), OscArg::Int(5)])]
+    );
+}
+
+#[test]
+fn osc_bridge_does_not_send_invalid_commands() {
+    let port = spawn_test_server(Some(ack_all_commands));
+
+    let hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let socket = UdpSocket::bind("127.0.0.1:0").expect("failed to bind bridge socket");
+    let bridge_addr = socket.local_addr().expect("failed to get bridge address");
+    let surface = UdpSocket::bind("127.0.0.1:0").expect("failed to bind surface socket");
+
+    let mut bridge = OscBridge::new(hub, socket, vec![]);
+
+    let messages = [
+        OscMessage::new("/videohub/route", vec![OscArg::Int(999), OscArg::Int(0)]),
+        OscMessage::new("/videohub/route", vec![OscArg::Int(0), OscArg::Int(20)]),
+        OscMessage::new("/videohub/label/output", vec![OscArg::Int(20), OscArg::String("Monitor".to_string())]),
+        OscMessage::new(
+            "/videohub/label/input",
+            vec![OscArg::Int(0), OscArg::String("Cam\n\nVIDEO OUTPUT ROUTING:\n0 5".to_string())],
+        ),
+    ];
+    for msg in &messages {
+        surface.send_to(&msg.encode(), bridge_addr).expect("failed to send");
+        bridge.step(Duration::from_millis(50)).expect("bridge step failed");
+    }
+    assert_eq!(bridge.hub().command_stats().sent, 0);
+    assert_eq!(bridge.hub().input_labels()[0], "Input 1");
+    assert_eq!(bridge.hub().video_routes()[0], 0);
+}
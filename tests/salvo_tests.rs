@@ -0,0 +1,39 @@
+extern crate hub_util;
+
+mod common;
+
+use common::{ack_all_commands, spawn_test_server};
+use hub_util::salvo::{apply_salvo, capture_salvo, salvo_diff, Salvo};
+use hub_util::video_hub::{IdSelection, VideoHub, VideoHubRoute};
+
+#[test]
+fn salvo_does_apply_changed_routes_in_one_block() {
+    let port = spawn_test_server(Some(ack_all_commands));
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let rehearsal = capture_salvo(&hub, "Rehearsal", &IdSelection::Only(vec![0..=3]), 1);
+    assert_eq!(rehearsal.routes.len(), 4);
+    assert_eq!(rehearsal.routes[0], VideoHubRoute { destination_id: 1, source_id: 1 });
+
+    let live = Salvo {
+        name: "Live".to_string(),
+        routes: (1..=4).map(|id| VideoHubRoute { destination_id: id, source_id: if id == 2 { 2 } else { 5 } }).collect(),
+    };
+    let diff = salvo_diff(&hub, &live, 1).expect("failed to compare salvo");
+    assert_eq!(diff.routes.len(), 3);
+
+    let sent = hub.command_stats().sent;
+    apply_salvo(&mut hub, &live, 1).expect("failed to apply salvo");
+    assert_eq!(hub.command_stats().sent, sent + 1);
+    assert_eq!(&hub.video_routes()[0..4], &[4, 1, 4, 4]);
+
+    apply_salvo(&mut hub, &rehearsal, 1).expect("failed to apply salvo");
+    assert_eq!(&hub.video_routes()[0..4], &[0, 1, 2, 3]);
+
+    let broken = Salvo {
+        name: "Broken".to_string(),
+        routes: vec![VideoHubRoute { destination_id: 21, source_id: 1 }],
+    };
+    assert!(salvo_diff(&hub, &broken, 1).is_err());
+}
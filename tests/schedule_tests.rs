@@ -0,0 +1,174 @@
+extern crate hub_util;
+
+mod common;
+
+use common::{ack_all_commands, spawn_test_server};
+use hub_util::read_to_newline;
+use hub_util::schedule::{EventOutcome, MissedPolicy, Schedule, Scheduler, EVENT_ATTEMPTS};
+use std::fs;
+use std::io::Write;
+use std::net::TcpStream;
+use std::time::Duration;
+
+const SCHEDULE: &str = r#"
+idBase: 1
+missed: run-latest
+events:
+- name: Satellite window
+  at: "06:00"
+  routes: ["1=5", "Output 2=Input 6"]
+- name: Network join
+  at: 1970-01-02T07:00:00Z
+  outputLabels:
+  - id: 3
+    name: NETWORK
+"#;
+
+#[test]
+fn scheduler_does_apply_due_and_missed_events() {
+    let server = || {
+        let port = spawn_test_server(Some(ack_all_commands));
+        format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP")
+    };
+    let schedule: Schedule = serde_yaml::from_str(SCHEDULE).expect("failed to parse schedule");
+    let state = std::env::temp_dir().join(format!("hub_util_schedule_{}.json", std::process::id()));
+
+    let mut scheduler = Scheduler::new(server(), schedule.clone(), Some(state.clone()), 5 * 3600).expect("failed to create scheduler");
+    assert!(scheduler.step(6 * 3600 - 1, Duration::from_millis(10)).expect("step failed").is_empty());
+    let logs = scheduler.step(6 * 3600 + 1, Duration::from_millis(10)).expect("step failed");
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].outcome, EventOutcome::Applied);
+    assert!(!logs[0].missed);
+    let hub = scheduler.hub().expect("not connected");
+    assert_eq!(&hub.video_routes()[0..2], &[4, 5]);
+
+    // restarted a day later, both events were missed
+    let restart = 86400 + 8 * 3600;
+    let mut scheduler = Scheduler::new(server(), schedule.clone(), Some(state.clone()), restart).expect("failed to create scheduler");
+    let logs = scheduler.step(restart, Duration::from_millis(10)).expect("step failed");
+    assert_eq!(logs.len(), 2);
+    assert!(logs.iter().all(|log| log.missed && log.outcome == EventOutcome::Applied));
+    assert_eq!(logs[1].to_string(), "1970-01-02T07:00:00.000Z Network join (missed): applied");
+    assert_eq!(scheduler.hub().expect("not connected").output_labels()[2], "NETWORK");
+
+    let skip = Schedule {
+        missed: MissedPolicy::Skip,
+        ..schedule
+    };
+    let restart = 3 * 86400;
+    let mut scheduler = Scheduler::new(server(), skip, Some(state.clone()), restart).expect("failed to create scheduler");
+    let logs = scheduler.step(restart, Duration::from_millis(10)).expect("step failed");
+    assert_eq!(logs.len(), 1);
+    assert!(logs.iter().all(|log| log.outcome == EventOutcome::Skipped));
+
+    fs::remove_file(&state).expect("failed to remove state");
+}
+
+#[test]
+fn scheduler_does_retry_failed_events() {
+    // refuses the first command and accepts everything after it
+    let port = spawn_test_server(Some(|client: &mut TcpStream| {
+        let mut first = true;
+        loop {
+            let cmd = read_to_newline(client, Some(Duration::from_secs(5))).unwrap_or_default();
+            if cmd.is_empty() {
+                return;
+            }
+            if first {
+                client.write_all("NACK\n\n".as_bytes()).expect("failed to send");
+                first = false;
+                continue;
+            }
+            client.write_all("ACK\n\n".as_bytes()).expect("failed to send");
+            client.write_all(cmd.as_bytes()).expect("failed to send");
+        }
+    }));
+    let schedule: Schedule = serde_yaml::from_str(SCHEDULE).expect("failed to parse schedule");
+    let server = format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP");
+
+    let mut scheduler = Scheduler::new(server, schedule, None, 6 * 3600 - 1).expect("failed to create scheduler");
+    scheduler.retry_interval = Duration::ZERO;
+    let logs = scheduler.step(6 * 3600 + 1, Duration::from_millis(10)).expect("step failed");
+    assert_eq!(logs.len(), 1);
+    assert!(matches!(logs[0].outcome, EventOutcome::Retrying(_)));
+    assert_eq!(&scheduler.hub().expect("not connected").video_routes()[0..2], &[0, 1]);
+
+    let logs = scheduler.step(6 * 3600 + 2, Duration::from_millis(10)).expect("step failed");
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].outcome, EventOutcome::Applied);
+    assert_eq!(&scheduler.hub().expect("not connected").video_routes()[0..2], &[4, 5]);
+    assert!(scheduler.step(6 * 3600 + 3, Duration::from_millis(10)).expect("step failed").is_empty());
+}
+
+#[test]
+fn scheduler_does_give_up_refused_events() {
+    // output 1 is locked by a panel, every route to it is refused
+    let port = spawn_test_server(Some(|client: &mut TcpStream| loop {
+        let cmd = read_to_newline(client, Some(Duration::from_secs(5))).unwrap_or_default();
+        if cmd.is_empty() {
+            return;
+        }
+        if cmd.starts_with("VIDEO OUTPUT ROUTING:\n0 ") {
+            client.write_all("NACK\n\n".as_bytes()).expect("failed to send");
+            continue;
+        }
+        client.write_all("ACK\n\n".as_bytes()).expect("failed to send");
+        client.write_all(cmd.as_bytes()).expect("failed to send");
+    }));
+    let schedule: Schedule = serde_yaml::from_str(
+        r#"
+idBase: 1
+events:
+- name: Locked
+  at: "06:00"
+  routes: ["1=5"]
+- name: Label
+  at: "06:00"
+  outputLabels:
+  - id: 3
+    name: NETWORK
+"#,
+    )
+    .expect("failed to parse schedule");
+    let server = format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP");
+
+    let mut scheduler = Scheduler::new(server, schedule, None, 6 * 3600 - 1).expect("failed to create scheduler");
+    scheduler.retry_interval = Duration::ZERO;
+    let logs = scheduler.step(6 * 3600 + 1, Duration::from_millis(10)).expect("step failed");
+    assert_eq!(logs.len(), 2);
+    assert!(matches!(logs[0].outcome, EventOutcome::Retrying(_)));
+    assert_eq!(logs[1].outcome, EventOutcome::Applied);
+    assert_eq!(scheduler.hub().expect("not connected").output_labels()[2], "NETWORK");
+
+    // the label event is not applied again while the route event is retried
+    for _ in 1..EVENT_ATTEMPTS - 1 {
+        let logs = scheduler.step(6 * 3600 + 2, Duration::from_millis(10)).expect("step failed");
+        assert_eq!(logs.len(), 1);
+        assert!(matches!(logs[0].outcome, EventOutcome::Retrying(_)));
+    }
+    let logs = scheduler.step(6 * 3600 + 3, Duration::from_millis(10)).expect("step failed");
+    assert_eq!(logs.len(), 1);
+    assert!(matches!(logs[0].outcome, EventOutcome::Failed(_)));
+    assert!(logs[0].to_string().starts_with("1970-01-01T06:00:00.000Z Locked: failed, "));
+    assert!(scheduler.step(6 * 3600 + 4, Duration::from_millis(10)).expect("step failed").is_empty());
+    assert_eq!(scheduler.hub().expect("not connected").command_stats().sent, EVENT_ATTEMPTS as u64 + 1);
+}
+
+#[test]
+fn scheduler_does_reject_events_outside_of_the_router() {
+    let port = spawn_test_server(Some(ack_all_commands));
+    let schedule: Schedule = serde_yaml::from_str(
+        r#"
+idBase: 1
+events:
+- at: "06:00"
+  routes: ["21=1"]
+"#,
+    )
+    .expect("failed to parse schedule");
+    let server = format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP");
+
+    let mut scheduler = Scheduler::new(server, schedule, None, 0).expect("failed to create scheduler");
+    let error = scheduler.step(1, Duration::from_millis(10)).expect_err("step should fail");
+    assert!(format!("{:#}", error).contains("Event 1 routes outside of the router"));
+}
@@ -0,0 +1,73 @@
+extern crate hub_util;
+
+mod common;
+
+use common::{ack_all_commands, spawn_test_server};
+use hub_util::shell::{Shell, ShellOutcome};
+use hub_util::read_to_newline;
+use hub_util::video_hub::{LockState, VideoHub};
+use std::io::Write;
+use std::net::TcpStream;
+use std::time::Duration;
+
+fn output(shell: &mut Shell, line: &str) -> String {
+    match shell.execute(line).expect("command failed") {
+        ShellOutcome::Output(output) => output,
+        ShellOutcome::Quit => panic!("shell quit"),
+    }
+}
+
+#[test]
+fn shell_does_run_commands() {
+    let port = spawn_test_server(Some(ack_all_commands));
+    let hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    let mut shell = Shell::new(hub, 1);
+
+    assert_eq!(output(&mut shell, "route 3 5"), "3 Output 3 <- 5 Input 5\n");
+    assert_eq!(output(&mut shell, r#"label in 2 "Cam 2""#), "2 Cam 2\n");
+    assert_eq!(output(&mut shell, r#"route "Output 4" "cam 2""#), "4 Output 4 <- 2 Cam 2\n");
+    assert_eq!(output(&mut shell, "lock 4"), "4 Output 4 owned\n");
+    assert_eq!(shell.hub().output_locks()[3], LockState::Owned);
+    assert!(output(&mut shell, "show").contains(" 4 Output 4  <-  2 Cam 2 O\n"));
+    assert!(shell.completion_words().contains(&"Cam 2".to_string()));
+
+    assert_eq!(output(&mut shell, "undo"), "Route 4: 2 -> 4\n");
+    assert_eq!(output(&mut shell, "history"), "1 Input label 2: \"Input 2\" -> \"Cam 2\"\n2 Route 3: 3 -> 5\n");
+    assert_eq!(output(&mut shell, "redo"), "Route 4: 4 -> 2\n");
+    assert_eq!(output(&mut shell, "redo"), "Nothing to redo\n");
+
+    assert!(shell.execute("route 21 1").is_err());
+    assert!(shell.execute("frobnicate").is_err());
+    assert_eq!(shell.execute("quit").expect("quit failed"), ShellOutcome::Quit);
+}
+
+#[test]
+fn shell_does_keep_external_changes_for_notifications() {
+    // a panel routes output 8 before the shell starts and output 9 while the
+    // command waits for its acknowledgement
+    let port = spawn_test_server(Some(|client: &mut TcpStream| {
+        let mut panel_routes = ["VIDEO OUTPUT ROUTING:\n7 9\n\n", "VIDEO OUTPUT ROUTING:\n8 10\n\n"].into_iter();
+        loop {
+            let cmd = read_to_newline(client, Some(Duration::from_secs(5))).unwrap_or_default();
+            if cmd.is_empty() {
+                return;
+            }
+            if let Some(route) = panel_routes.next() {
+                client.write_all(route.as_bytes()).expect("failed to send");
+            }
+            client.write_all("ACK\n\n".as_bytes()).expect("failed to send");
+            client.write_all(cmd.as_bytes()).expect("failed to send");
+        }
+    }));
+    let mut hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    hub.ping().expect("ping failed");
+    let mut shell = Shell::new(hub, 1);
+
+    assert_eq!(output(&mut shell, "route 3 5"), "3 Output 3 <- 5 Input 5\n");
+    let notifications = shell.notifications(Duration::from_millis(50)).expect("failed to poll");
+    assert_eq!(notifications.len(), 2);
+    assert!(notifications[0].contains("Output 8"));
+    assert!(notifications[1].contains("Output 9"));
+}
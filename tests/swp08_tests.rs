@@ -0,0 +1,136 @@
+extern crate hub_util;
+
+mod common;
+
+use common::{ack_all_commands, spawn_test_server};
+use hub_util::swp08::Swp08Gateway;
+use hub_util::video_hub::VideoHub;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+// Frame without any DLE in the data, which holds for the values used here
+fn frame(data: &[u8]) -> Vec<u8> {
+    let btc = data.len() as u8;
+    let chk = data.iter().fold(btc, |sum, byte| sum.wrapping_add(*byte)).wrapping_neg();
+    let mut frame = vec![0x10, 0x02];
+    frame.extend_from_slice(data);
+    frame.extend_from_slice(&[btc, chk, 0x10, 0x03]);
+    frame
+}
+
+fn read_exact(controller: &mut TcpStream, gateway: &mut Swp08Gateway, len: usize) -> Vec<u8> {
+    let mut received = Vec::new();
+    let start = Instant::now();
+    while received.len() < len && start.elapsed() < Duration::from_secs(5) {
+        gateway.step(Duration::from_millis(20)).expect("gateway step failed");
+        let mut buf = [0u8; 512];
+        if let Ok(size) = controller.read(&mut buf) {
+            received.extend_from_slice(&buf[..size]);
+        }
+    }
+    received
+}
+
+#[test]
+fn swp08_gateway_does_connect_and_interrogate() {
+    let port = spawn_test_server(Some(ack_all_commands));
+    let hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind gateway");
+    let addr = listener.local_addr().expect("failed to get gateway address");
+    let mut gateway = Swp08Gateway::new(hub, listener).expect("failed to create gateway");
+
+    let mut controller = TcpStream::connect(addr).expect("failed to connect to gateway");
+    controller
+        .set_read_timeout(Some(Duration::from_millis(20)))
+        .expect("failed to set read timeout");
+
+    // connect source 7 to destination 2
+    controller
+        .write_all(&frame(&[0x02, 0x00, 0x00, 0x02, 0x07]))
+        .expect("failed to send connect");
+    let expected = [vec![0x10, 0x06], frame(&[0x04, 0x00, 0x00, 0x02, 0x07])].concat();
+    assert_eq!(read_exact(&mut controller, &mut gateway, expected.len()), expected);
+    assert_eq!(gateway.hub().video_routes()[2], 7);
+
+    // interrogate destination 2
+    controller
+        .write_all(&frame(&[0x01, 0x00, 0x00, 0x02]))
+        .expect("failed to send interrogate");
+    let expected = [vec![0x10, 0x06], frame(&[0x03, 0x00, 0x00, 0x02, 0x07])].concat();
+    assert_eq!(read_exact(&mut controller, &mut gateway, expected.len()), expected);
+
+    // single destination name, 8 characters
+    controller
+        .write_all(&frame(&[0x67, 0x00, 0x01, 0x00, 0x03]))
+        .expect("failed to send name request");
+    let mut name_response = vec![0x6B, 0x00, 0x01, 0x00, 0x03, 0x01];
+    name_response.extend_from_slice(b"Output 4");
+    let expected = [vec![0x10, 0x06], frame(&name_response)].concat();
+    assert_eq!(read_exact(&mut controller, &mut gateway, expected.len()), expected);
+}
+
+#[test]
+fn swp08_gateway_does_reject_bad_checksum() {
+    let port = spawn_test_server(Some(ack_all_commands));
+    let hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind gateway");
+    let addr = listener.local_addr().expect("failed to get gateway address");
+    let mut gateway = Swp08Gateway::new(hub, listener).expect("failed to create gateway");
+
+    let mut controller = TcpStream::connect(addr).expect("failed to connect to gateway");
+    controller
+        .set_read_timeout(Some(Duration::from_millis(20)))
+        .expect("failed to set read timeout");
+
+    let mut bad = frame(&[0x02, 0x00, 0x00, 0x02, 0x07]);
+    let len = bad.len();
+    bad[len - 3] = bad[len - 3].wrapping_add(1);
+    controller.write_all(&bad).expect("failed to send connect");
+
+    assert_eq!(read_exact(&mut controller, &mut gateway, 2), vec![0x10, 0x15]);
+    assert_eq!(gateway.hub().video_routes()[2], 2);
+}
+
+#[test]
+fn swp08_gateway_does_confirm_unchanged_connect_to_every_controller() {
+    let port = spawn_test_server(Some(ack_all_commands));
+    let hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind gateway");
+    let addr = listener.local_addr().expect("failed to get gateway address");
+    let mut gateway = Swp08Gateway::new(hub, listener).expect("failed to create gateway");
+
+    let mut controllers: Vec<TcpStream> = (0..2)
+        .map(|_| {
+            let controller = TcpStream::connect(addr).expect("failed to connect to gateway");
+            controller
+                .set_read_timeout(Some(Duration::from_millis(20)))
+                .expect("failed to set read timeout");
+            controller
+        })
+        .collect();
+    gateway.step(Duration::from_millis(20)).expect("gateway step failed");
+
+    // source 2 is already routed to destination 2
+    controllers[0]
+        .write_all(&frame(&[0x02, 0x00, 0x00, 0x02, 0x02]))
+        .expect("failed to send connect");
+    let connected = frame(&[0x04, 0x00, 0x00, 0x02, 0x02]);
+    let expected = [vec![0x10, 0x06], connected.clone()].concat();
+    assert_eq!(read_exact(&mut controllers[0], &mut gateway, expected.len()), expected);
+    assert_eq!(read_exact(&mut controllers[1], &mut gateway, connected.len()), connected);
+
+    // sent once, not again when the router echoes the route
+    for _ in 0..5 {
+        gateway.step(Duration::from_millis(20)).expect("gateway step failed");
+    }
+    for controller in &mut controllers {
+        assert!(controller.read(&mut [0u8; 64]).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+extern crate hub_util;
+
+mod common;
+
+use common::{spawn_test_server, EMPTY_FUNC};
+use hub_util::tsl::{TslDisplay, TslSender, TslTransport, TslVersion};
+use hub_util::video_hub::VideoHub;
+use std::io::Write;
+use std::net::{TcpStream, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn tsl_sender_does_follow_routing_changes() {
+    // another panel routes input 6 to output 2
+    let port = spawn_test_server(Some(|client: &mut TcpStream| {
+        thread::sleep(Duration::from_millis(100));
+        client
+            .write_all("VIDEO OUTPUT ROUTING:\n1 5\n\n".as_bytes())
+            .expect("failed to send");
+    }));
+
+    let hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+
+    let receiver = UdpSocket::bind("127.0.0.1:0").expect("failed to bind receiver");
+    receiver
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("failed to set read timeout");
+    let socket = UdpSocket::bind("127.0.0.1:0").expect("failed to bind sender");
+    let transport = TslTransport::Udp(socket, receiver.local_addr().expect("failed to get receiver address"));
+
+    let displays = vec![TslDisplay {
+        destination: 1,
+        address: 10,
+    }];
+    let mut sender = TslSender::new(hub, transport, TslVersion::V31, displays).expect("failed to create sender");
+    sender.send_all().expect("failed to send displays");
+
+    let mut buf = [0u8; 64];
+    let size = receiver.recv(&mut buf).expect("no initial packet received");
+    assert_eq!(size, 18);
+    assert_eq!(buf[0], 0x80 + 10);
+    assert_eq!(&buf[2..18], b"Input 2         ");
+
+    let start = Instant::now();
+    while sender.hub().video_routes()[1] != 5 && start.elapsed() < Duration::from_secs(5) {
+        sender.step(Duration::from_millis(50)).expect("sender step failed");
+    }
+
+    let size = receiver.recv(&mut buf).expect("no update received");
+    assert_eq!(size, 18);
+    assert_eq!(&buf[2..18], b"Input 6         ");
+}
+
+#[test]
+fn tsl_sender_does_reject_invalid_displays() {
+    let sender = |version: TslVersion, destination: usize, address: u16| {
+        let port = spawn_test_server(EMPTY_FUNC);
+        let hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+            .expect("failed to parse videohub");
+        let socket = UdpSocket::bind("127.0.0.1:0").expect("failed to bind sender");
+        let addr = socket.local_addr().expect("failed to get sender address");
+        TslSender::new(hub, TslTransport::Udp(socket, addr), version, vec![TslDisplay { destination, address }])
+    };
+
+    assert!(sender(TslVersion::V31, 19, 126).is_ok());
+    assert!(sender(TslVersion::V31, 20, 1).is_err());
+    assert!(sender(TslVersion::V31, 0, 127).is_err());
+    assert!(sender(TslVersion::V50, 0, 127).is_ok());
+}
@@ -0,0 +1,46 @@
+extern crate hub_util;
+
+mod common;
+
+use common::spawn_test_server;
+use hub_util::video_hub::VideoHub;
+use hub_util::watch::Watcher;
+use serde_json::Value;
+use std::io::Write;
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn watcher_does_print_changes_as_ndjson() {
+    // a panel locks output 3 and routes input 6 to it
+    let port = spawn_test_server(Some(|client: &mut TcpStream| {
+        thread::sleep(Duration::from_millis(100));
+        client
+            .write_all("VIDEO OUTPUT LOCKS:\n2 L\n\nVIDEO OUTPUT ROUTING:\n2 5\n\n".as_bytes())
+            .expect("failed to send");
+        thread::sleep(Duration::from_secs(5));
+    }));
+
+    let hub = VideoHub::new(format!("127.0.0.1:{}", &port).parse().expect("Failed to parse server IP"))
+        .expect("failed to parse videohub");
+    let mut watcher = Watcher::new(hub, Vec::new(), true, 1);
+
+    let start = Instant::now();
+    while watcher.output().iter().filter(|byte| **byte == b'\n').count() < 2 && start.elapsed() < Duration::from_secs(5) {
+        watcher.step(Duration::from_millis(50)).expect("watcher step failed");
+    }
+
+    let output = String::from_utf8(watcher.output().clone()).expect("output is not UTF-8");
+    let events: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("line is not JSON"))
+        .collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["type"], "lock");
+    assert_eq!(events[0]["destination"], 3);
+    assert_eq!(events[0]["state"], "locked");
+    assert_eq!(events[1]["type"], "route");
+    assert_eq!(events[1]["sourceLabel"], "Input 6");
+    assert!(events[1]["time"].as_str().expect("time is not a string").ends_with('Z'));
+}